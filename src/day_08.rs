@@ -3,114 +3,332 @@ use combine::easy;
 use combine::parser::char::*;
 use combine::*;
 
-use combine::lib::collections::HashSet;
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
 use std::num::ParseIntError;
 use std::result::Result as StdResult;
+use std::str::FromStr;
+
+use debugger::Debugger;
+use vm::{Execute, Halt, Machine, Registers, ACCUMULATOR};
 
 const INPUT: &str = include_str!("../data/day_08_input");
 
+pub mod debugger;
+pub mod vm;
+
 pub fn run() -> Result<()> {
     println!("*** Day 8: Handheld Halting ***");
     println!("Input: {}", INPUT);
     let program = parse(INPUT)?;
 
-    let mut interpreter = Interpreter::new(&program);
-    interpreter.run_until_repeat();
-    println!("Solution 1: {:?}", interpreter.accumulator);
+    let mut machine = Machine::new(&program.0);
+    let halt = machine.run();
+    println!(
+        "Solution 1: {:?} ({:?})",
+        machine.state().accumulator(),
+        halt
+    );
 
-    let mut interpreter_2 = Interpreter::new(&program);
-    let r = interpreter_2.corrected_until_end();
+    let r = program.corrected_until_end();
     println!("Solution 2: {:?}", r);
+    println!("Repairs: {:?}", program.repairs());
+    let analysis = program.analyse();
+    println!(
+        "Analysis: {:?}, {} unreachable instructions, {} loop(s) of length {:?}",
+        analysis.outcome,
+        analysis.unreachable.len(),
+        analysis.loops.len(),
+        analysis.loops.iter().map(|l| l.len()).collect::<Vec<_>>()
+    );
 
     Ok(())
 }
 
-struct Interpreter<'a> {
-    next_index: usize,
-    visited_indices: HashSet<usize>,
+pub fn debug() -> Result<()> {
+    println!("*** Day 8: Handheld Halting (debugger) ***");
+    let program = parse(INPUT)?;
+    let mut debugger = Debugger::new(Machine::new(&program.0));
+    let stdin = io::stdin();
+    debugger.repl(stdin.lock(), io::stdout())
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+enum Instruction {
+    Noop(isize),
+    Acc(isize),
+    Jump(isize),
+}
+
+impl Instruction {
+    fn next_pc(&self, pc: usize) -> isize {
+        match self {
+            Instruction::Jump(jump) => pc as isize + jump,
+            _ => pc as isize + 1,
+        }
+    }
+
+    fn accumulator_change(&self) -> isize {
+        match self {
+            Instruction::Acc(by) => *by,
+            _ => 0,
+        }
+    }
+
+    // The nop <-> jmp swap that might fix a corrupted program
+    fn flipped(&self) -> Option<Instruction> {
+        match self {
+            Instruction::Noop(arg) => Some(Instruction::Jump(*arg)),
+            Instruction::Jump(arg) => Some(Instruction::Noop(*arg)),
+            Instruction::Acc(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Instruction::Noop(arg) => write!(f, "nop {:+}", arg),
+            Instruction::Acc(by) => write!(f, "acc {:+}", by),
+            Instruction::Jump(jump) => write!(f, "jmp {:+}", jump),
+        }
+    }
+}
+
+impl Execute for Instruction {
+    fn execute(&self, registers: &mut Registers) -> Option<isize> {
+        registers[ACCUMULATOR] = registers[ACCUMULATOR].checked_add(self.accumulator_change())?;
+        match self {
+            Instruction::Jump(jump) => Some(*jump),
+            _ => Some(1),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+struct Repair {
+    pc: usize,
+    replacement: Instruction,
     accumulator: isize,
-    program: &'a Program,
 }
 
-impl<'a> Interpreter<'a> {
-    fn new(program: &Program) -> Interpreter {
-        Interpreter {
-            next_index: 0,
-            visited_indices: HashSet::new(),
-            accumulator: 0,
-            program,
+#[derive(Debug, PartialEq, Eq, Clone)]
+struct Analysis {
+    // What running the program would end with, found without running it
+    outcome: Halt,
+    unreachable: Vec<usize>,
+    // Every cycle in the program, each starting from its lowest pc. Any execution that reaches
+    // one of these never terminates.
+    loops: Vec<Vec<usize>>,
+    out_of_bounds_jumps: Vec<usize>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+struct Program(Vec<Instruction>);
+
+// Disassembles into something `parse` can assemble back into the same program: every pc gets a
+// label, and jumps that land on a label refer to it by name.
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let len = self.0.len() as isize;
+        for (pc, instruction) in self.0.iter().enumerate() {
+            writeln!(f, "pc_{}:", pc)?;
+            match instruction {
+                Instruction::Jump(_) => {
+                    let target = instruction.next_pc(pc);
+                    if target >= 0 && target <= len {
+                        writeln!(f, "    jmp pc_{}", target)?
+                    } else {
+                        writeln!(f, "    {}", instruction)?
+                    }
+                }
+                _ => writeln!(f, "    {}", instruction)?,
+            }
         }
+        writeln!(f, "pc_{}:", len)
     }
+}
+
+impl Program {
+    // Every pc has exactly one successor, so the control flow graph is just a chain from pc 0
+    // that either leaves the program or runs into a cycle.
+    fn analyse(&self) -> Analysis {
+        let len = self.0.len();
+        let in_bounds = |pc: isize| pc >= 0 && (pc as usize) < len;
 
-    fn run_until_repeat(&mut self) {
-        while let Some(instruction) = self.program.0.get(self.next_index) {
-            if self.visited_indices.contains(&self.next_index) {
-                break;
+        let mut reachable = vec![false; len];
+        let mut pc = 0;
+        let outcome = loop {
+            if pc == len as isize {
+                break Halt::Terminated;
+            } else if !in_bounds(pc) {
+                break Halt::OutOfBounds { pc };
+            } else if reachable[pc as usize] {
+                break Halt::InfiniteLoop { pc: pc as usize };
             }
-            let current_index = self.next_index;
-            match instruction {
-                Instruction::Noop => self.next_index += 1,
-                Instruction::Acc(by) => {
-                    self.accumulator += by;
-                    self.next_index += 1;
+            reachable[pc as usize] = true;
+            pc = self.0[pc as usize].next_pc(pc as usize);
+        };
+
+        let unreachable = (0..len).filter(|pc| !reachable[*pc]).collect();
+
+        let out_of_bounds_jumps = (0..len)
+            .filter(|pc| {
+                let next = self.0[*pc].next_pc(*pc);
+                next < 0 || next as usize > len
+            })
+            .collect();
+
+        // Walk from every pc we haven't seen yet; running into a pc seen on the current walk
+        // means we've found a new cycle.
+        let mut walk_started_from = vec![None; len];
+        let mut loops = Vec::new();
+        for start in 0..len {
+            let mut pc = start as isize;
+            while in_bounds(pc) && walk_started_from[pc as usize].is_none() {
+                walk_started_from[pc as usize] = Some(start);
+                pc = self.0[pc as usize].next_pc(pc as usize);
+            }
+            if in_bounds(pc) && walk_started_from[pc as usize] == Some(start) {
+                let mut cycle = vec![pc as usize];
+                let mut next = self.0[pc as usize].next_pc(pc as usize);
+                while next != pc {
+                    cycle.push(next as usize);
+                    next = self.0[next as usize].next_pc(next as usize);
                 }
-                Instruction::Jump(jump) => {
-                    let idx_as_i = self.next_index as isize;
-                    self.next_index = (idx_as_i + jump) as usize;
+                let lowest = cycle.iter().enumerate().min_by_key(|(_, pc)| **pc);
+                if let Some((idx, _)) = lowest {
+                    cycle.rotate_left(idx);
                 }
+                loops.push(cycle);
             }
-            self.visited_indices.insert(current_index);
+        }
+        loops.sort();
+
+        Analysis {
+            outcome,
+            unreachable,
+            loops,
+            out_of_bounds_jumps,
         }
     }
 
-    fn corrected_until_end(&mut self) -> Option<isize> {
-        self.program
-            .corrected_variations()
-            .into_iter()
-            .filter_map(|corrected_variation| {
-                let program = Program(corrected_variation);
-                let mut interpreter = Interpreter::new(&program);
-                interpreter.run_until_repeat();
+    fn corrected_until_end(&self) -> Option<isize> {
+        self.repairs().first().map(|repair| repair.accumulator)
+    }
 
-                if interpreter.next_index == interpreter.program.0.len() {
-                    Some(interpreter.accumulator)
+    // Every single nop <-> jmp flip that makes the program terminate. A program that already
+    // terminates doesn't need repairing, so has none.
+    //
+    // Only flips on the path the program actually takes can change anything, and a flip there
+    // works iff it sends us to a pc that reaches the end without coming back through the flipped
+    // one. Since each pc has exactly one successor, we can find all of those by walking backwards
+    // from the end once.
+    //
+    // Flips that would overflow the accumulator on the way, where the machine would halt with
+    // `Halt::Overflow`, aren't repairs either.
+    fn repairs(&self) -> Vec<Repair> {
+        let len = self.0.len();
+        let accumulated_until_end = self.accumulated_until_end();
+        if accumulated_until_end[0].is_some() {
+            return Vec::new();
+        }
+        let mut visited = vec![false; len];
+        let mut repairs = Vec::new();
+        let mut pc = 0;
+        let mut accumulator = 0;
+        while pc >= 0 && (pc as usize) < len && !visited[pc as usize] {
+            let current_pc = pc as usize;
+            visited[current_pc] = true;
+            let instruction = &self.0[current_pc];
+            if let Some(replacement) = instruction.flipped() {
+                let target = replacement.next_pc(current_pc);
+                // A path to the end through `current_pc` would mean `current_pc` reaches the end
+                // too, which none on the path from pc 0 do when the program doesn't terminate.
+                let rest = if target >= 0
+                    && target as usize <= len
+                    && accumulated_until_end[current_pc].is_none()
+                {
+                    accumulated_until_end[target as usize]
                 } else {
                     None
+                };
+                if let Some(accumulator) = rest.and_then(|rest| rest.from(accumulator)) {
+                    repairs.push(Repair {
+                        pc: current_pc,
+                        replacement,
+                        accumulator,
+                    });
                 }
-            })
-            .next()
+            }
+            // Overflowing here overflows whatever gets flipped after
+            accumulator = match accumulator.checked_add(instruction.accumulator_change()) {
+                Some(accumulator) => accumulator,
+                None => break,
+            };
+            pc = instruction.next_pc(current_pc);
+        }
+        repairs
+    }
+
+    // For every pc (plus the one just past the end), how the accumulator changes on the way to
+    // termination, or None if starting there never terminates.
+    fn accumulated_until_end(&self) -> Vec<Option<Accumulated>> {
+        let len = self.0.len();
+        let mut predecessors = vec![Vec::new(); len + 1];
+        for (pc, instruction) in self.0.iter().enumerate() {
+            let next = instruction.next_pc(pc);
+            if next >= 0 && next as usize <= len {
+                predecessors[next as usize].push(pc);
+            }
+        }
+
+        let mut accumulated_until_end = vec![None; len + 1];
+        accumulated_until_end[len] = Some(Accumulated::default());
+        let mut to_visit = vec![len];
+        while let Some(pc) = to_visit.pop() {
+            let accumulated = accumulated_until_end[pc].unwrap_or_default();
+            for &predecessor in predecessors[pc].iter() {
+                accumulated_until_end[predecessor] =
+                    Some(accumulated.after(self.0[predecessor].accumulator_change()));
+                to_visit.push(predecessor);
+            }
+        }
+        accumulated_until_end
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
-enum Instruction {
-    Noop,
-    Acc(isize),
-    Jump(isize),
+// How the accumulator changes along a path: by `total` in the end, and never going further up
+// than `highest` or down than `lowest` on the way. These are i128s so that they can't overflow
+// themselves: a path visits each pc at most once, and there can't be 2^64 of those.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+struct Accumulated {
+    total: i128,
+    highest: i128,
+    lowest: i128,
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
-struct Program(Vec<Instruction>);
+impl Accumulated {
+    // This path, with an instruction changing the accumulator by `change` before it
+    fn after(self, change: isize) -> Accumulated {
+        let change = change as i128;
+        Accumulated {
+            total: change + self.total,
+            highest: change + self.highest.max(0),
+            lowest: change + self.lowest.min(0),
+        }
+    }
 
-impl Program {
-    fn corrected_variations(&self) -> Vec<Vec<Instruction>> {
-        self.0
-            .iter()
-            .enumerate()
-            .map(|(idx, instr)| match instr {
-                Instruction::Jump(_) => {
-                    let mut cloned = self.0.clone();
-                    cloned[idx] = Instruction::Noop;
-                    cloned
-                }
-                Instruction::Noop => {
-                    let mut cloned = self.0.clone();
-                    cloned[idx] = Instruction::Jump(0);
-                    cloned
-                }
-                Instruction::Acc(_) => self.0.clone(),
-            })
-            .collect()
+    // What the accumulator ends up as when it starts out as `accumulator`, or None if it
+    // overflows on the way
+    fn from(self, accumulator: isize) -> Option<isize> {
+        let start = accumulator as i128;
+        if start + self.highest > isize::MAX as i128 || start + self.lowest < isize::MIN as i128 {
+            None
+        } else {
+            Some((start + self.total) as isize)
+        }
     }
 }
 
@@ -143,26 +361,107 @@ where
         })
 }
 
+// Operands for jmp and nop can either be a relative offset, as in the puzzle input, or a label
+#[derive(Debug, PartialEq, Eq, Clone)]
+enum Operand {
+    Offset(isize),
+    Label(String),
+}
+
+// Signed numbers are offsets, as in the puzzle input, anything else is a label
+impl FromStr for Operand {
+    type Err = ParseIntError;
+
+    fn from_str(s: &str) -> StdResult<Operand, ParseIntError> {
+        if s.starts_with('+') || s.starts_with('-') {
+            s.parse().map(Operand::Offset)
+        } else {
+            Ok(Operand::Label(s.to_string()))
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+enum Line {
+    Label(String),
+    Acc(isize),
+    Jump(Operand),
+    Noop(Operand),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+enum AssemblyError {
+    UnknownLabel(String),
+    DuplicateLabel(String),
+}
+
+impl fmt::Display for AssemblyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AssemblyError::UnknownLabel(label) => write!(f, "Unknown label [{}]", label),
+            AssemblyError::DuplicateLabel(label) => write!(f, "Duplicate label [{}]", label),
+        }
+    }
+}
+
+impl std::error::Error for AssemblyError {}
+
+// Labels point at the instruction that follows them, so a label at the very end points just past
+// the last instruction, which is where a jump needs to go to terminate.
+fn assemble(lines: Vec<Line>) -> StdResult<Program, AssemblyError> {
+    let mut label_pcs = HashMap::new();
+    let mut pc: isize = 0;
+    for line in lines.iter() {
+        match line {
+            Line::Label(label) => {
+                if label_pcs.insert(label.clone(), pc).is_some() {
+                    return Err(AssemblyError::DuplicateLabel(label.clone()));
+                }
+            }
+            _ => pc += 1,
+        }
+    }
+
+    let mut instructions = Vec::with_capacity(pc as usize);
+    let mut pc = 0;
+    let resolve = |operand: Operand, pc: isize| match operand {
+        Operand::Offset(offset) => Ok(offset),
+        Operand::Label(label) => label_pcs
+            .get(&label)
+            .map(|target| *target - pc)
+            .ok_or(AssemblyError::UnknownLabel(label)),
+    };
+    for line in lines {
+        let instruction = match line {
+            Line::Label(_) => continue,
+            Line::Acc(by) => Instruction::Acc(by),
+            Line::Jump(operand) => Instruction::Jump(resolve(operand, pc)?),
+            Line::Noop(operand) => Instruction::Noop(resolve(operand, pc)?),
+        };
+        instructions.push(instruction);
+        pc += 1;
+    }
+    Ok(Program(instructions))
+}
+
 fn parse(s: &str) -> StdResult<Program, easy::ParseError<&str>> {
-    let single_instruction_parser = attempt(
-        string("acc")
-            .skip(space())
-            .and(i_number_parser())
-            .map(|(_, num)| Instruction::Acc(num))
-            .or(attempt(
-                string("jmp")
-                    .skip(space())
-                    .and(i_number_parser())
-                    .map(|(_, num)| Instruction::Jump(num)),
-            ))
-            .or(attempt(
-                string("nop")
-                    .skip(space())
-                    .and(i_number_parser())
-                    .map(|(_, _)| Instruction::Noop),
-            )),
-    );
-    let mut parser = many(single_instruction_parser.skip(spaces())).map(Program);
+    let label = || many1::<String, _, _>(alpha_num().or(char('_')));
+    let operand = || {
+        many1::<String, _, _>(alpha_num().or(one_of("_+-".chars())))
+            .and_then(|s| Operand::from_str(&s))
+    };
+    let acc_parser = attempt(string("acc").skip(space()))
+        .with(i_number_parser())
+        .map(Line::Acc);
+    let jump_parser = attempt(string("jmp").skip(space()))
+        .with(operand())
+        .map(Line::Jump);
+    let noop_parser = attempt(string("nop").skip(space()))
+        .with(operand())
+        .map(Line::Noop);
+    let label_parser = attempt(label().skip(char(':'))).map(Line::Label);
+    let line_parser = acc_parser.or(jump_parser).or(noop_parser).or(label_parser);
+    let mut parser = many(line_parser.skip(spaces())).and_then(assemble);
     let (r, _) = parser.easy_parse(s)?;
     Ok(r)
 }
@@ -185,7 +484,7 @@ jmp -4
 acc +6
 ";
         let expected = Program(vec![
-            Noop,
+            Noop(0),
             Acc(1),
             Jump(4),
             Acc(3),
@@ -212,10 +511,11 @@ jmp -4
 acc +6
 ";
         let program = parse(input).unwrap();
-        let mut interpreter = Interpreter::new(&program);
-        interpreter.run_until_repeat();
+        let mut machine = Machine::new(&program.0);
+        let halt = machine.run();
 
-        assert_eq!(5, interpreter.accumulator);
+        assert_eq!(Halt::InfiniteLoop { pc: 1 }, halt);
+        assert_eq!(5, machine.state().accumulator());
     }
 
     #[test]
@@ -231,9 +531,197 @@ jmp -4
 acc +6
 ";
         let program = parse(input).unwrap();
-        let mut interpreter = Interpreter::new(&program);
-        let r = interpreter.corrected_until_end();
+        let r = program.corrected_until_end();
 
         assert_eq!(Some(8), r);
     }
+
+    #[test]
+    fn repairs_test() {
+        let input = "nop +3
+jmp +0
+acc +5
+acc +1
+";
+        let program = parse(input).unwrap();
+        let expected = vec![
+            Repair {
+                pc: 0,
+                replacement: Jump(3),
+                accumulator: 1,
+            },
+            Repair {
+                pc: 1,
+                replacement: Noop(0),
+                accumulator: 6,
+            },
+        ];
+        assert_eq!(expected, program.repairs());
+    }
+
+    #[test]
+    fn repairs_agree_with_running_test() {
+        let program = parse(INPUT).unwrap();
+        let repairs = program.repairs();
+        assert!(!repairs.is_empty());
+        for repair in repairs {
+            let mut repaired = program.0.clone();
+            repaired[repair.pc] = repair.replacement;
+            let mut machine = Machine::new(&repaired);
+            assert_eq!(Halt::Terminated, machine.run());
+            assert_eq!(repair.accumulator, machine.state().accumulator());
+        }
+    }
+
+    #[test]
+    fn repairs_halt_test() {
+        let programs = vec![
+            "nop +3\njmp +0\nacc +5\nacc +1\n",
+            "nop +0\nacc +1\njmp -2\n",
+            "acc +1\nnop +2\njmp -2\nacc +3\n",
+            "jmp +2\nacc +7\njmp -1\n",
+        ];
+        for input in programs {
+            let program = parse(input).unwrap();
+            let repairs = program.repairs();
+            assert!(!repairs.is_empty(), "{}", input);
+            for repair in repairs {
+                let mut repaired = program.0.clone();
+                repaired[repair.pc] = repair.replacement.clone();
+                let mut machine = Machine::new(&repaired);
+                assert_eq!(Halt::Terminated, machine.run(), "{:?} in {}", repair, input);
+                assert_eq!(repair.accumulator, machine.state().accumulator());
+            }
+        }
+    }
+
+    #[test]
+    fn overflowing_repairs_test() {
+        let max = isize::MAX;
+        let programs = vec![
+            // Ends up back at the max, but overflows on the way
+            (format!("acc +{}\njmp +0\nacc +1\nacc -1\n", max), 1, None),
+            (
+                format!("acc +{}\njmp +0\nacc -1\nacc +1\n", max),
+                1,
+                Some(max),
+            ),
+            (format!("acc -{}\njmp +0\nacc -2\n", max), 1, None),
+            (
+                format!("acc -{}\njmp +0\nacc -1\n", max),
+                1,
+                Some(isize::MIN),
+            ),
+            // Overflows before reaching anything to flip
+            (format!("acc +{}\nacc +1\njmp +0\n", max), 2, None),
+        ];
+        for (input, flip, expected) in programs {
+            let program = parse(&input).unwrap();
+            let repairs = program.repairs();
+            assert_eq!(
+                expected,
+                repairs.first().map(|repair| repair.accumulator),
+                "{}",
+                input
+            );
+
+            let mut repaired = program.0.clone();
+            repaired[flip] = repaired[flip].flipped().unwrap();
+            let mut machine = Machine::new(&repaired);
+            match expected {
+                Some(accumulator) => {
+                    assert_eq!(Halt::Terminated, machine.run());
+                    assert_eq!(accumulator, machine.state().accumulator());
+                }
+                None => assert!(matches!(machine.run(), Halt::Overflow { .. })),
+            }
+        }
+    }
+
+    #[test]
+    fn already_terminating_repairs_test() {
+        // Flipping pc 0 to jmp +0 would loop forever, and nothing else needs fixing
+        let program = parse("nop +0\nacc +1\n").unwrap();
+        assert_eq!(Halt::Terminated, program.analyse().outcome);
+        assert_eq!(Vec::<Repair>::new(), program.repairs());
+        assert_eq!(None, program.corrected_until_end());
+    }
+
+    #[test]
+    fn assemble_labels_test() {
+        let input = "start:
+nop end
+acc +1
+loop_start:
+jmp +2
+jmp loop_start
+acc -1
+jmp start
+end:
+";
+        let expected = Program(vec![Noop(6), Acc(1), Jump(2), Jump(-1), Acc(-1), Jump(-5)]);
+        assert_eq!(expected, parse(input).unwrap());
+    }
+
+    #[test]
+    fn assemble_errors_test() {
+        assert!(parse("jmp nowhere\n").is_err());
+        assert!(parse("a:\nnop +0\na:\njmp a\n").is_err());
+    }
+
+    #[test]
+    fn disassemble_round_trip_test() {
+        let program = Program(vec![Noop(0), Acc(1), Jump(2), Jump(-10), Jump(-2)]);
+        let disassembled = program.to_string();
+        assert_eq!(
+            "pc_0:
+    nop +0
+pc_1:
+    acc +1
+pc_2:
+    jmp pc_4
+pc_3:
+    jmp -10
+pc_4:
+    jmp pc_2
+pc_5:
+",
+            disassembled
+        );
+        assert_eq!(program, parse(&disassembled).unwrap());
+
+        let program = parse(INPUT).unwrap();
+        assert_eq!(program, parse(&program.to_string()).unwrap());
+    }
+
+    #[test]
+    fn analyse_test() {
+        let input = "nop +0
+acc +1
+jmp +4
+acc +3
+jmp -3
+acc -99
+acc +1
+jmp -4
+acc +6
+";
+        let analysis = parse(input).unwrap().analyse();
+        let expected = Analysis {
+            outcome: Halt::InfiniteLoop { pc: 1 },
+            unreachable: vec![5, 8],
+            loops: vec![vec![1, 2, 6, 7, 3, 4]],
+            out_of_bounds_jumps: vec![],
+        };
+        assert_eq!(expected, analysis);
+
+        let analysis = Program(vec![Jump(2), Jump(0), Acc(1), Jump(-10), Jump(-1)]).analyse();
+        let expected = Analysis {
+            outcome: Halt::OutOfBounds { pc: -7 },
+            unreachable: vec![1, 4],
+            loops: vec![vec![1]],
+            out_of_bounds_jumps: vec![3],
+        };
+        assert_eq!(expected, analysis);
+    }
 }
@@ -0,0 +1,336 @@
+use anyhow::Result;
+use std::fmt::Debug;
+use std::io::{BufRead, Write};
+
+use super::vm::{Execute, Halt, Machine, Register, Step};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Breakpoint {
+    Pc(isize),
+    Accumulator(isize),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Stop {
+    Breakpoint(Breakpoint),
+    Halted(Halt),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Target {
+    Pc,
+    Register(Register),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Command {
+    Step(usize),
+    Continue,
+    Print,
+    Set(Target, isize),
+    Break(Breakpoint),
+    ClearBreakpoints,
+    Trace(bool),
+    Help,
+    Quit,
+}
+
+const HELP: &str = "commands:
+  s, step [n]               execute n instructions (default 1)
+  c, continue               run until a breakpoint or the machine halts
+  p, print                  show pc, registers and the next instruction
+  set <pc|acc|a|b|c|d> <n>  overwrite the pc or a register
+  b, break <pc|acc> <n>     stop when the pc or accumulator becomes n
+  clear                     remove all breakpoints
+  trace <on|off>            log every executed instruction
+  h, help                   show this message
+  q, quit                   leave the debugger";
+
+pub struct Debugger<'a, I> {
+    machine: Machine<'a, I>,
+    breakpoints: Vec<Breakpoint>,
+    trace: bool,
+}
+
+impl<'a, I: Execute + Debug> Debugger<'a, I> {
+    pub fn new(machine: Machine<'a, I>) -> Debugger<'a, I> {
+        Debugger {
+            machine,
+            breakpoints: Vec::new(),
+            trace: false,
+        }
+    }
+
+    pub fn with_trace(mut self, trace: bool) -> Debugger<'a, I> {
+        self.trace = trace;
+        self
+    }
+
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) {
+        if !self.breakpoints.contains(&breakpoint) {
+            self.breakpoints.push(breakpoint);
+        }
+    }
+
+    pub fn machine(&self) -> &Machine<'a, I> {
+        &self.machine
+    }
+
+    pub fn step<W: Write>(&mut self, out: &mut W) -> Result<Step> {
+        let pc = self.machine.state().pc;
+        let instruction = self
+            .machine
+            .current_instruction()
+            .map(|i| format!("{:?}", i));
+        let step = self.machine.step();
+        if let (true, Step::Executed(state), Some(instruction)) = (self.trace, step, instruction) {
+            writeln!(
+                out,
+                "{:>5} | {:<24} | acc {}",
+                pc,
+                instruction,
+                state.accumulator()
+            )?;
+        }
+        Ok(step)
+    }
+
+    // Always executes at least one instruction, so continuing from a breakpoint moves past it.
+    // Accumulator breakpoints fire when the accumulator changes to the value.
+    pub fn continue_until_stop<W: Write>(&mut self, out: &mut W) -> Result<Stop> {
+        loop {
+            let previous_accumulator = self.machine.state().accumulator();
+            match self.step(out)? {
+                Step::Halted(halt) => return Ok(Stop::Halted(halt)),
+                Step::Executed(state) => {
+                    let hit = self.breakpoints.iter().find(|b| match b {
+                        Breakpoint::Pc(pc) => state.pc == *pc,
+                        Breakpoint::Accumulator(acc) => {
+                            state.accumulator() == *acc && previous_accumulator != *acc
+                        }
+                    });
+                    if let Some(breakpoint) = hit {
+                        return Ok(Stop::Breakpoint(*breakpoint));
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn repl<R: BufRead, W: Write>(&mut self, input: R, mut out: W) -> Result<()> {
+        writeln!(out, "{}", HELP)?;
+        write!(out, "> ")?;
+        out.flush()?;
+        for line in input.lines() {
+            let line = line?;
+            let trimmed = line.trim();
+            if !trimmed.is_empty() {
+                match parse_command(trimmed) {
+                    Ok(Command::Quit) => return Ok(()),
+                    Ok(command) => self.execute(command, &mut out)?,
+                    Err(e) => writeln!(out, "Could not understand [{}]: {}", trimmed, e)?,
+                }
+            }
+            write!(out, "> ")?;
+            out.flush()?;
+        }
+        Ok(())
+    }
+
+    fn execute<W: Write>(&mut self, command: Command, out: &mut W) -> Result<()> {
+        match command {
+            Command::Step(times) => {
+                for _ in 0..times {
+                    if let Step::Halted(halt) = self.step(out)? {
+                        writeln!(out, "Halted: {:?}", halt)?;
+                        break;
+                    }
+                }
+                self.print(out)?;
+            }
+            Command::Continue => {
+                match self.continue_until_stop(out)? {
+                    Stop::Breakpoint(breakpoint) => writeln!(out, "Hit {:?}", breakpoint)?,
+                    Stop::Halted(halt) => writeln!(out, "Halted: {:?}", halt)?,
+                }
+                self.print(out)?;
+            }
+            Command::Print => self.print(out)?,
+            Command::Set(Target::Pc, value) => self.machine.set_pc(value),
+            Command::Set(Target::Register(register), value) => {
+                self.machine.set_register(register, value)
+            }
+            Command::Break(breakpoint) => self.add_breakpoint(breakpoint),
+            Command::ClearBreakpoints => self.breakpoints.clear(),
+            Command::Trace(on) => self.trace = on,
+            Command::Help => writeln!(out, "{}", HELP)?,
+            Command::Quit => {}
+        }
+        Ok(())
+    }
+
+    fn print<W: Write>(&self, out: &mut W) -> Result<()> {
+        let state = self.machine.state();
+        writeln!(
+            out,
+            "pc {} | a {} b {} c {} d {} | steps {}",
+            state.pc,
+            state.registers[Register::A],
+            state.registers[Register::B],
+            state.registers[Register::C],
+            state.registers[Register::D],
+            self.machine.steps()
+        )?;
+        match self.machine.current_instruction() {
+            Some(instruction) => writeln!(out, "next: {:?}", instruction)?,
+            None => writeln!(out, "next: <none>")?,
+        }
+        Ok(())
+    }
+}
+
+// Commands are a handful of whitespace separated words, so we don't need combine here
+fn parse_command(s: &str) -> Result<Command> {
+    let words: Vec<_> = s.split_whitespace().collect();
+    let number = |word: &str| -> Result<isize> { Ok(word.parse()?) };
+    let register = |word: &str| -> Result<Register> {
+        match word {
+            "acc" | "a" => Ok(Register::A),
+            "b" => Ok(Register::B),
+            "c" => Ok(Register::C),
+            "d" => Ok(Register::D),
+            other => anyhow::bail!("unknown register [{}]", other),
+        }
+    };
+    let command = match words.as_slice() {
+        ["s"] | ["step"] => Command::Step(1),
+        ["s", times] | ["step", times] => Command::Step(times.parse()?),
+        ["c"] | ["continue"] => Command::Continue,
+        ["p"] | ["print"] => Command::Print,
+        ["set", "pc", value] => Command::Set(Target::Pc, number(value)?),
+        ["set", r, value] => Command::Set(Target::Register(register(r)?), number(value)?),
+        ["b", "pc", pc] | ["break", "pc", pc] => Command::Break(Breakpoint::Pc(number(pc)?)),
+        ["b", "acc", acc] | ["break", "acc", acc] => {
+            Command::Break(Breakpoint::Accumulator(number(acc)?))
+        }
+        ["clear"] => Command::ClearBreakpoints,
+        ["trace", "on"] => Command::Trace(true),
+        ["trace", "off"] => Command::Trace(false),
+        ["h"] | ["help"] => Command::Help,
+        ["q"] | ["quit"] => Command::Quit,
+        _ => anyhow::bail!("unknown command"),
+    };
+    Ok(command)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::parse;
+    use super::*;
+
+    const INPUT: &str = "nop +0
+acc +1
+jmp +4
+acc +3
+jmp -3
+acc -99
+acc +1
+jmp -4
+acc +6
+";
+
+    #[test]
+    fn parse_command_test() {
+        assert_eq!(Command::Step(1), parse_command("s").unwrap());
+        assert_eq!(Command::Step(3), parse_command("step 3").unwrap());
+        assert_eq!(Command::Continue, parse_command("continue").unwrap());
+        assert_eq!(Command::Print, parse_command("p").unwrap());
+        assert_eq!(
+            Command::Set(Target::Register(Register::A), -4),
+            parse_command("set acc -4").unwrap()
+        );
+        assert_eq!(
+            Command::Set(Target::Register(Register::C), 2),
+            parse_command("set c 2").unwrap()
+        );
+        assert_eq!(
+            Command::Set(Target::Pc, 7),
+            parse_command("set pc 7").unwrap()
+        );
+        assert_eq!(
+            Command::Break(Breakpoint::Accumulator(5)),
+            parse_command("b acc 5").unwrap()
+        );
+        assert_eq!(
+            Command::Break(Breakpoint::Pc(4)),
+            parse_command("break pc 4").unwrap()
+        );
+        assert_eq!(Command::Trace(false), parse_command("trace off").unwrap());
+        assert_eq!(Command::Quit, parse_command("q").unwrap());
+        assert!(parse_command("sing").is_err());
+        assert!(parse_command("set pc").is_err());
+    }
+
+    #[test]
+    fn trace_test() {
+        let program = parse(INPUT).unwrap();
+        let mut debugger = Debugger::new(Machine::new(&program.0)).with_trace(true);
+        let mut out = Vec::new();
+        let stop = debugger.continue_until_stop(&mut out).unwrap();
+        assert_eq!(Stop::Halted(Halt::InfiniteLoop { pc: 1 }), stop);
+
+        let trace = String::from_utf8(out).unwrap();
+        let pcs: Vec<_> = trace
+            .lines()
+            .map(|l| l.split('|').next().unwrap().trim().to_string())
+            .collect();
+        assert_eq!(vec!["0", "1", "2", "6", "7", "3", "4"], pcs);
+        assert!(trace.lines().last().unwrap().ends_with("acc 5"));
+    }
+
+    #[test]
+    fn breakpoints_test() {
+        let program = parse(INPUT).unwrap();
+        let mut debugger = Debugger::new(Machine::new(&program.0));
+        debugger.add_breakpoint(Breakpoint::Pc(3));
+        debugger.add_breakpoint(Breakpoint::Accumulator(1));
+        let mut out = Vec::new();
+
+        assert_eq!(
+            Stop::Breakpoint(Breakpoint::Accumulator(1)),
+            debugger.continue_until_stop(&mut out).unwrap()
+        );
+        assert_eq!(2, debugger.machine().state().pc);
+        assert_eq!(
+            Stop::Breakpoint(Breakpoint::Pc(3)),
+            debugger.continue_until_stop(&mut out).unwrap()
+        );
+        assert_eq!(2, debugger.machine().state().accumulator());
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn repl_test() {
+        let program = parse(INPUT).unwrap();
+        let mut debugger = Debugger::new(Machine::new(&program.0));
+        let commands = "b pc 7
+c
+set acc 100
+garbage
+set pc 8
+s 2
+q
+p
+";
+        let mut out = Vec::new();
+        debugger.repl(commands.as_bytes(), &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        assert!(out.contains("Hit Pc(7)"));
+        assert!(out.contains("Could not understand [garbage]"));
+        assert!(out.contains("Halted: Terminated"));
+        assert_eq!(106, debugger.machine().state().accumulator());
+        // Nothing after quit is executed
+        assert_eq!(1, out.matches("pc 9 |").count());
+    }
+}
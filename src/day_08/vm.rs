@@ -0,0 +1,449 @@
+use std::collections::HashSet;
+use std::fmt::Debug;
+use std::ops::{Index, IndexMut};
+
+pub const REGISTER_COUNT: usize = 4;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum Register {
+    A,
+    B,
+    C,
+    D,
+}
+
+impl Register {
+    fn idx(self) -> usize {
+        match self {
+            Register::A => 0,
+            Register::B => 1,
+            Register::C => 2,
+            Register::D => 3,
+        }
+    }
+}
+
+// Register A doubles as the accumulator for the handheld's `acc` instruction
+pub const ACCUMULATOR: Register = Register::A;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, Default)]
+pub struct Registers([isize; REGISTER_COUNT]);
+
+impl Index<Register> for Registers {
+    type Output = isize;
+
+    fn index(&self, register: Register) -> &isize {
+        &self.0[register.idx()]
+    }
+}
+
+impl IndexMut<Register> for Registers {
+    fn index_mut(&mut self, register: Register) -> &mut isize {
+        &mut self.0[register.idx()]
+    }
+}
+
+// Anything that can be run on the machine.
+//
+// `execute` mutates the registers and returns the relative offset the program counter should
+// move by (1 for "carry on to the next instruction"), or None if a register would overflow, in
+// which case the registers are left as they were.
+pub trait Execute {
+    fn execute(&self, registers: &mut Registers) -> Option<isize>;
+
+    // Whether the offset returned by `execute` depends on the registers. If no instruction in a
+    // program branches on registers, revisiting a pc is enough to know we're in a loop; otherwise
+    // we need to see the whole machine state repeat.
+    fn is_conditional(&self) -> bool {
+        false
+    }
+}
+
+// A richer instruction set than the handheld's, with multiple registers and conditional jumps.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Op {
+    Nop,
+    Set(Register, isize),
+    Add(Register, isize),
+    AddRegister(Register, Register),
+    Jump(isize),
+    JumpIfZero(Register, isize),
+    JumpIfNotZero(Register, isize),
+}
+
+impl Execute for Op {
+    fn execute(&self, registers: &mut Registers) -> Option<isize> {
+        let offset = match *self {
+            Op::Nop => 1,
+            Op::Set(register, value) => {
+                registers[register] = value;
+                1
+            }
+            Op::Add(register, by) => {
+                registers[register] = registers[register].checked_add(by)?;
+                1
+            }
+            Op::AddRegister(target, source) => {
+                registers[target] = registers[target].checked_add(registers[source])?;
+                1
+            }
+            Op::Jump(offset) => offset,
+            Op::JumpIfZero(register, offset) => {
+                if registers[register] == 0 {
+                    offset
+                } else {
+                    1
+                }
+            }
+            Op::JumpIfNotZero(register, offset) => {
+                if registers[register] != 0 {
+                    offset
+                } else {
+                    1
+                }
+            }
+        };
+        Some(offset)
+    }
+
+    fn is_conditional(&self) -> bool {
+        matches!(self, Op::JumpIfZero(_, _) | Op::JumpIfNotZero(_, _))
+    }
+}
+
+// The pc is signed so that jumping before the start of the program is representable
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, Default)]
+pub struct MachineState {
+    pub pc: isize,
+    pub registers: Registers,
+}
+
+impl MachineState {
+    pub fn accumulator(&self) -> isize {
+        self.registers[ACCUMULATOR]
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Halt {
+    // pc landed exactly one past the last instruction
+    Terminated,
+    // about to execute the instruction at pc again without anything having changed
+    InfiniteLoop { pc: usize },
+    OutOfBounds { pc: isize },
+    StepLimit { steps: usize },
+    // the instruction at pc would have overflowed a register, or the pc itself
+    Overflow { pc: usize },
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Step {
+    Executed(MachineState),
+    Halted(Halt),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum LoopDetection {
+    Pc,
+    State,
+}
+
+// Watching for the whole state to repeat means remembering every state we've been in, and a
+// program that keeps changing its registers can go a very long time without repeating one. So
+// unless told otherwise, machines that need to do that give up after this many steps.
+pub const DEFAULT_STATE_STEP_LIMIT: usize = 1_000_000;
+
+pub struct Machine<'a, I> {
+    program: &'a [I],
+    state: MachineState,
+    steps: usize,
+    step_limit: Option<usize>,
+    overflowed: bool,
+    loop_detection: LoopDetection,
+    visited_pcs: HashSet<isize>,
+    visited_states: HashSet<MachineState>,
+}
+
+impl<'a, I: Execute + Debug> Machine<'a, I> {
+    pub fn new(program: &'a [I]) -> Machine<'a, I> {
+        let loop_detection = if program.iter().any(|i| i.is_conditional()) {
+            LoopDetection::State
+        } else {
+            LoopDetection::Pc
+        };
+        let step_limit = match loop_detection {
+            LoopDetection::Pc => None,
+            LoopDetection::State => Some(DEFAULT_STATE_STEP_LIMIT),
+        };
+        Machine {
+            program,
+            state: MachineState::default(),
+            steps: 0,
+            step_limit,
+            overflowed: false,
+            loop_detection,
+            visited_pcs: HashSet::new(),
+            visited_states: HashSet::new(),
+        }
+    }
+
+    pub fn with_step_limit(mut self, step_limit: usize) -> Machine<'a, I> {
+        self.step_limit = Some(step_limit);
+        self
+    }
+
+    // Programs with conditional jumps get DEFAULT_STATE_STEP_LIMIT unless this is called, and
+    // then can use as much memory as they take steps
+    pub fn without_step_limit(mut self) -> Machine<'a, I> {
+        self.step_limit = None;
+        self
+    }
+
+    pub fn with_registers(mut self, registers: Registers) -> Machine<'a, I> {
+        self.state.registers = registers;
+        self
+    }
+
+    pub fn state(&self) -> &MachineState {
+        &self.state
+    }
+
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    pub fn current_instruction(&self) -> Option<&I> {
+        if self.state.pc < 0 {
+            None
+        } else {
+            self.program.get(self.state.pc as usize)
+        }
+    }
+
+    // Poking at the state means earlier visits no longer say anything about loops, so we
+    // forget them.
+    pub fn set_pc(&mut self, pc: isize) {
+        self.state.pc = pc;
+        self.overflowed = false;
+        self.forget_visited();
+    }
+
+    pub fn set_register(&mut self, register: Register, value: isize) {
+        self.state.registers[register] = value;
+        self.overflowed = false;
+        self.forget_visited();
+    }
+
+    // What would happen if we stepped now, without stepping
+    pub fn halted(&self) -> Option<Halt> {
+        let pc = self.state.pc;
+        if self.overflowed {
+            Some(Halt::Overflow { pc: pc as usize })
+        } else if pc == self.program.len() as isize {
+            Some(Halt::Terminated)
+        } else if pc < 0 || pc > self.program.len() as isize {
+            Some(Halt::OutOfBounds { pc })
+        } else if self.seen_before() {
+            Some(Halt::InfiniteLoop { pc: pc as usize })
+        } else {
+            match self.step_limit {
+                Some(limit) if self.steps >= limit => Some(Halt::StepLimit { steps: self.steps }),
+                _ => None,
+            }
+        }
+    }
+
+    pub fn step(&mut self) -> Step {
+        if let Some(halt) = self.halted() {
+            return Step::Halted(halt);
+        }
+        match self.loop_detection {
+            LoopDetection::Pc => {
+                self.visited_pcs.insert(self.state.pc);
+            }
+            LoopDetection::State => {
+                self.visited_states.insert(self.state);
+            }
+        }
+        let instruction = &self.program[self.state.pc as usize];
+        let mut registers = self.state.registers;
+        let pc = instruction
+            .execute(&mut registers)
+            .and_then(|offset| self.state.pc.checked_add(offset));
+        let pc = match pc {
+            Some(pc) => pc,
+            None => {
+                self.overflowed = true;
+                return Step::Halted(Halt::Overflow {
+                    pc: self.state.pc as usize,
+                });
+            }
+        };
+        trace!(
+            "pc {} {:?} acc {}",
+            self.state.pc,
+            instruction,
+            registers[ACCUMULATOR]
+        );
+        self.state = MachineState { pc, registers };
+        self.steps += 1;
+        Step::Executed(self.state)
+    }
+
+    pub fn run(&mut self) -> Halt {
+        loop {
+            if let Step::Halted(halt) = self.step() {
+                return halt;
+            }
+        }
+    }
+
+    fn forget_visited(&mut self) {
+        self.visited_pcs.clear();
+        self.visited_states.clear();
+    }
+
+    fn seen_before(&self) -> bool {
+        match self.loop_detection {
+            LoopDetection::Pc => self.visited_pcs.contains(&self.state.pc),
+            LoopDetection::State => self.visited_states.contains(&self.state),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Op::*;
+    use super::Register::*;
+    use super::*;
+
+    #[test]
+    fn multiply_with_conditional_jumps_test() {
+        // C = A * B, by adding A to C, B times
+        let program = vec![
+            Set(A, 6),
+            Set(B, 7),
+            JumpIfZero(B, 4),
+            AddRegister(C, A),
+            Add(B, -1),
+            Jump(-3),
+        ];
+        let mut machine = Machine::new(&program);
+        assert_eq!(Halt::Terminated, machine.run());
+        assert_eq!(42, machine.state().registers[C]);
+        assert_eq!(0, machine.state().registers[B]);
+    }
+
+    #[test]
+    fn conditional_revisits_are_not_loops_test() {
+        // Revisits pc 1 three times, but with different registers each time
+        let program = vec![Set(B, 3), Add(B, -1), JumpIfNotZero(B, -1)];
+        let mut machine = Machine::new(&program);
+        assert_eq!(Halt::Terminated, machine.run());
+        assert_eq!(7, machine.steps());
+    }
+
+    #[test]
+    fn conditional_infinite_loop_test() {
+        let program = vec![Set(B, 1), JumpIfNotZero(B, 0)];
+        let mut machine = Machine::new(&program);
+        assert_eq!(Halt::InfiniteLoop { pc: 1 }, machine.run());
+    }
+
+    #[test]
+    fn out_of_bounds_test() {
+        let program = vec![Nop, Jump(-2)];
+        let mut machine = Machine::new(&program);
+        assert_eq!(Halt::OutOfBounds { pc: -1 }, machine.run());
+
+        let program = vec![Jump(3), Nop];
+        let mut machine = Machine::new(&program);
+        assert_eq!(Halt::OutOfBounds { pc: 3 }, machine.run());
+    }
+
+    #[test]
+    fn step_limit_test() {
+        let program = vec![Set(B, 100), Add(B, -1), JumpIfNotZero(B, -1)];
+        let mut machine = Machine::new(&program).with_step_limit(10);
+        assert_eq!(Halt::StepLimit { steps: 10 }, machine.run());
+        // Stays halted
+        assert_eq!(Step::Halted(Halt::StepLimit { steps: 10 }), machine.step());
+    }
+
+    #[test]
+    fn default_step_limit_test() {
+        // Counts down forever without ever repeating a state
+        let program = vec![Add(B, -1), JumpIfNotZero(A, -1), Nop];
+        let mut machine = Machine::new(&program).with_registers(Registers([1, 0, 0, 0]));
+        assert_eq!(
+            Halt::StepLimit {
+                steps: DEFAULT_STATE_STEP_LIMIT
+            },
+            machine.run()
+        );
+
+        let mut machine = Machine::new(&program)
+            .with_registers(Registers([1, 0, 0, 0]))
+            .with_step_limit(5);
+        assert_eq!(Halt::StepLimit { steps: 5 }, machine.run());
+
+        let program = vec![Set(B, 3), Add(B, -1), JumpIfNotZero(B, -1)];
+        let mut machine = Machine::new(&program).without_step_limit();
+        assert_eq!(Halt::Terminated, machine.run());
+
+        // Without conditional jumps, revisiting a pc is enough, so there's no need for a limit
+        let program = vec![Add(B, 1)];
+        let mut machine = Machine::new(&program).with_registers(Registers([0, 0, 0, 0]));
+        assert_eq!(Halt::Terminated, machine.run());
+    }
+
+    #[test]
+    fn overflow_test() {
+        let program = vec![Add(B, 1), Add(A, 1), AddRegister(A, A)];
+        let mut machine =
+            Machine::new(&program).with_registers(Registers([isize::MAX / 2 + 1, 0, 0, 0]));
+        assert_eq!(Halt::Overflow { pc: 2 }, machine.run());
+        // The registers are as they were before the instruction that overflowed, and it stays
+        // halted
+        assert_eq!(isize::MAX / 2 + 2, machine.state().registers[A]);
+        assert_eq!(1, machine.state().registers[B]);
+        assert_eq!(2, machine.steps());
+        assert_eq!(Step::Halted(Halt::Overflow { pc: 2 }), machine.step());
+
+        // Jumping past the end of what a pc can hold
+        let program = vec![Nop, Jump(isize::MAX)];
+        let mut machine = Machine::new(&program);
+        assert_eq!(Halt::Overflow { pc: 1 }, machine.run());
+    }
+
+    #[test]
+    fn step_test() {
+        let program = vec![Add(A, 2), Jump(2), Nop, Add(D, 1)];
+        let mut machine = Machine::new(&program).with_registers(Registers([1, 0, 0, 5]));
+        let mut expected = Registers([3, 0, 0, 5]);
+        assert_eq!(
+            Step::Executed(MachineState {
+                pc: 1,
+                registers: expected
+            }),
+            machine.step()
+        );
+        assert_eq!(
+            Step::Executed(MachineState {
+                pc: 3,
+                registers: expected
+            }),
+            machine.step()
+        );
+        expected[D] = 6;
+        assert_eq!(
+            Step::Executed(MachineState {
+                pc: 4,
+                registers: expected
+            }),
+            machine.step()
+        );
+        assert_eq!(Step::Halted(Halt::Terminated), machine.step());
+        assert_eq!(3, machine.state().accumulator());
+    }
+}
@@ -1,71 +1,216 @@
-use anyhow::Result;
-use itertools::Itertools;
+use anyhow::{Context, Result};
+use num::{BigUint, CheckedAdd, CheckedSub, Zero};
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::io::{self, BufRead};
+use std::ops::Range;
+use std::str::FromStr;
 
 const INPUT: &str = include_str!("../data/day_09_input");
 
-pub fn run() -> Result<()> {
+pub fn run(preamble_size: usize) -> Result<()> {
     println!("*** Day 9: Encoding Error ***");
     println!("Input: {}", INPUT);
-    let numbers = parse(INPUT);
+    let numbers: Vec<u64> = parse(INPUT)?;
 
-    let solution_1 = find_preamble_error(&numbers, 25);
+    let solution_1 = find_preamble_error(&numbers, preamble_size);
     println!("Solution 1: {:?}", solution_1);
 
-    let solution_2 = find_min_max_sums_in_first_window_adding_to(&numbers, solution_1.unwrap_or(0));
+    let solution_2 =
+        find_min_max_sums_in_first_window_adding_to(&numbers, &solution_1.unwrap_or(0));
     println!("Solution 2: {:?}", solution_2);
     Ok(())
 }
 
-fn find_preamble_error(v: &[usize], preamble_size: usize) -> Option<usize> {
-    v.windows(preamble_size + 1)
-        .into_iter()
-        .filter_map(|slice| {
-            if let Some(number) = slice.last() {
-                let preamble = slice.iter().take(preamble_size).copied();
-                let first_sum_in_preamble_to_num = preamble
-                    .combinations(2)
-                    .find(|v| v.iter().sum::<usize>() == *number);
-                if first_sum_in_preamble_to_num.is_none() {
-                    // nothing in the preamble adds to this number
-                    Some(*number)
-                } else {
-                    None
+// Reads numbers off stdin one line at a time, reporting invalid ones as soon as they're seen.
+// Numbers can be arbitrarily big here since we don't know where they're coming from.
+pub fn run_stdin(preamble_size: usize) -> Result<()> {
+    println!("*** Day 9: Encoding Error (stdin) ***");
+    let stdin = io::stdin();
+    let mut validator = Validator::new(preamble_size);
+    let mut numbers: Vec<BigUint> = Vec::new();
+    let mut first_invalid = None;
+    for (idx, line) in stdin.lock().lines().enumerate() {
+        if let Some(number) = parse_line(idx, &line?)? {
+            if let Some(invalid) = validator.push(&number) {
+                println!(
+                    "Invalid: {} at position {}",
+                    invalid.value, invalid.position
+                );
+                first_invalid = first_invalid.or(Some(invalid));
+            }
+            numbers.push(number);
+        }
+    }
+    if let Some(invalid) = first_invalid {
+        let range = find_contiguous_range_adding_to(&numbers, &invalid.value);
+        println!("Contiguous range adding to {}: {:?}", invalid.value, range);
+        println!(
+            "Min + max: {}",
+            find_min_max_sums_in_first_window_adding_to(&numbers, &invalid.value)
+                .map(|n| n.to_string())
+                .unwrap_or_else(|| "-".to_string())
+        );
+    }
+    Ok(())
+}
+
+// Anything we can validate: u64, u128, BigUint and friends. Sums always go through the checked
+// operations, so fixed-width numbers never overflow.
+trait XmasNumber: Clone + Ord + Hash + Debug + Zero + CheckedAdd + CheckedSub {}
+
+impl<N> XmasNumber for N where N: Clone + Ord + Hash + Debug + Zero + CheckedAdd + CheckedSub {}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+struct InvalidNumber<N> {
+    position: usize,
+    value: N,
+}
+
+// Keeps the last `preamble_size` numbers along with how many pairs of them add up to each sum,
+// so checking a number is a lookup and moving the window along is linear in the preamble size.
+//
+// Pairs whose sum overflows are left out: they can't add up to anything we could be given.
+struct Validator<N> {
+    preamble_size: usize,
+    window: VecDeque<N>,
+    pair_sums: HashMap<N, usize>,
+    position: usize,
+}
+
+impl<N: XmasNumber> Validator<N> {
+    fn new(preamble_size: usize) -> Validator<N> {
+        Validator {
+            preamble_size,
+            window: VecDeque::with_capacity(preamble_size),
+            pair_sums: HashMap::new(),
+            position: 0,
+        }
+    }
+
+    // Numbers in the initial preamble are always valid
+    fn push(&mut self, number: &N) -> Option<InvalidNumber<N>> {
+        let r = if self.window.len() == self.preamble_size && !self.pair_sums.contains_key(number) {
+            Some(InvalidNumber {
+                position: self.position,
+                value: number.clone(),
+            })
+        } else {
+            None
+        };
+
+        if self.window.len() == self.preamble_size {
+            if let Some(leaving) = self.window.pop_front() {
+                for other in self.window.iter() {
+                    if let Some(sum) = leaving.checked_add(other) {
+                        if let Some(count) = self.pair_sums.get_mut(&sum) {
+                            *count -= 1;
+                            if *count == 0 {
+                                self.pair_sums.remove(&sum);
+                            }
+                        }
+                    }
                 }
-            } else {
-                None
             }
-        })
-        .next()
+        }
+        if self.preamble_size > 0 {
+            for other in self.window.iter() {
+                if let Some(sum) = number.checked_add(other) {
+                    *self.pair_sums.entry(sum).or_insert(0) += 1;
+                }
+            }
+            self.window.push_back(number.clone());
+        }
+        self.position += 1;
+        r
+    }
 }
 
-fn find_min_max_sums_in_first_window_adding_to(v: &[usize], target: usize) -> Option<usize> {
-    let valid_numbers: Vec<_> = v.iter().filter(|i| **i <= target).copied().collect();
-    (2..valid_numbers.len())
+fn invalid_numbers<'a, N, I>(
+    numbers: I,
+    preamble_size: usize,
+) -> impl Iterator<Item = InvalidNumber<N>> + 'a
+where
+    N: XmasNumber + 'a,
+    I: IntoIterator<Item = &'a N>,
+    I::IntoIter: 'a,
+{
+    numbers
         .into_iter()
-        .flat_map(|window_size| {
-            let sum_of_min_max_in_vecs_that_sum_to_target: Vec<_> = valid_numbers
-                .as_slice()
-                .windows(window_size)
-                .filter_map(|window| {
-                    let sum = window.iter().sum::<usize>();
-                    if sum == target {
-                        window
-                            .iter()
-                            .min()
-                            .zip(window.iter().max())
-                            .map(|(min, max)| *min + max)
-                    } else {
-                        None
-                    }
-                })
-                .collect();
-            sum_of_min_max_in_vecs_that_sum_to_target
+        .scan(Validator::new(preamble_size), |validator, number| {
+            Some(validator.push(number))
         })
+        .flatten()
+}
+
+fn find_preamble_error<N: XmasNumber>(v: &[N], preamble_size: usize) -> Option<N> {
+    invalid_numbers(v, preamble_size)
         .next()
+        .map(|invalid| invalid.value)
+}
+
+// Two pointers over the numbers: grow the window on the right while it's too small, shrink it on
+// the left while it's too big. Only works because the numbers are never negative.
+fn find_contiguous_range_adding_to<N: XmasNumber>(v: &[N], target: &N) -> Option<Range<usize>> {
+    let mut start = 0;
+    let mut sum = N::zero();
+    for (end, number) in v.iter().enumerate() {
+        // A sum that overflows is definitely too big, so shrink until it fits. This always
+        // terminates since an empty window sums to zero.
+        sum = loop {
+            match sum.checked_add(number) {
+                Some(new_sum) => break new_sum,
+                None => {
+                    sum = sum.checked_sub(&v[start])?;
+                    start += 1;
+                }
+            }
+        };
+        while sum > *target && start < end {
+            sum = sum.checked_sub(&v[start])?;
+            start += 1;
+        }
+        if sum == *target && end > start {
+            return Some(start..end + 1);
+        }
+    }
+    None
+}
+
+fn find_min_max_sums_in_first_window_adding_to<N: XmasNumber>(v: &[N], target: &N) -> Option<N> {
+    let window = &v[find_contiguous_range_adding_to(v, target)?];
+    let min = window.iter().min()?;
+    let max = window.iter().max()?;
+    // Can't overflow: the window has at least 2 numbers, so min + max <= target
+    Some(min.clone() + max.clone())
+}
+
+fn parse_line<N>(idx: usize, line: &str) -> Result<Option<N>>
+where
+    N: FromStr,
+    <N as FromStr>::Err: std::error::Error + Send + Sync + 'static,
+{
+    let trimmed = line.trim();
+    if trimmed.is_empty() {
+        Ok(None)
+    } else {
+        let number = trimmed
+            .parse()
+            .with_context(|| format!("Line {}: could not parse [{}]", idx + 1, trimmed))?;
+        Ok(Some(number))
+    }
 }
 
-fn parse(s: &str) -> Vec<usize> {
-    s.split('\n').filter_map(|s| s.parse().ok()).collect()
+fn parse<N>(s: &str) -> Result<Vec<N>>
+where
+    N: FromStr,
+    <N as FromStr>::Err: std::error::Error + Send + Sync + 'static,
+{
+    s.split('\n')
+        .enumerate()
+        .filter_map(|(idx, line)| parse_line(idx, line).transpose())
+        .collect()
 }
 
 #[cfg(test)]
@@ -94,7 +239,7 @@ mod tests {
 277
 309
 576";
-        let r = parse(input);
+        let r: Vec<usize> = parse(input).unwrap();
 
         let expected = vec![
             35, 20, 15, 25, 47, 40, 62, 55, 65, 95, 102, 117, 150, 182, 127, 219, 299, 277, 309,
@@ -120,7 +265,108 @@ mod tests {
             576,
         ];
 
-        let windows_adding_to_127 = find_min_max_sums_in_first_window_adding_to(&v, 127);
+        let windows_adding_to_127 = find_min_max_sums_in_first_window_adding_to(&v, &127);
         assert_eq!(Some(62), windows_adding_to_127);
+        assert_eq!(None, find_min_max_sums_in_first_window_adding_to(&v, &1));
+    }
+
+    #[test]
+    fn invalid_numbers_test() {
+        let v = vec![
+            35, 20, 15, 25, 47, 40, 62, 55, 65, 95, 102, 117, 150, 182, 127, 219, 299, 277, 309,
+            576,
+        ];
+        let r: Vec<_> = invalid_numbers(&v, 5).collect();
+        assert_eq!(
+            vec![InvalidNumber {
+                position: 14,
+                value: 127
+            }],
+            r
+        );
+    }
+
+    #[test]
+    fn invalid_numbers_repeated_values_test() {
+        // 40 = 20 + 20 is fine, since they are different entries in the preamble, but once one of
+        // them has left the window it no longer is
+        let r: Vec<_> = invalid_numbers(&[20, 20, 40, 60, 40], 2).collect();
+        assert_eq!(
+            vec![InvalidNumber {
+                position: 4,
+                value: 40
+            }],
+            r
+        );
+    }
+
+    #[test]
+    fn find_contiguous_range_adding_to_test() {
+        let v = vec![
+            35, 20, 15, 25, 47, 40, 62, 55, 65, 95, 102, 117, 150, 182, 127, 219, 299, 277, 309,
+            576,
+        ];
+        assert_eq!(Some(2..6), find_contiguous_range_adding_to(&v, &127));
+        assert_eq!(None, find_contiguous_range_adding_to(&v, &1));
+        // A single number is not a range
+        assert_eq!(None, find_contiguous_range_adding_to(&[1, 5, 2], &5));
+    }
+
+    #[test]
+    fn solutions_test() {
+        let numbers: Vec<u64> = parse(INPUT).unwrap();
+        assert_eq!(Some(542529149), find_preamble_error(&numbers, 25));
+        assert_eq!(
+            Some(75678618),
+            find_min_max_sums_in_first_window_adding_to(&numbers, &542529149)
+        );
+
+        let big_numbers: Vec<BigUint> = parse(INPUT).unwrap();
+        let invalid = find_preamble_error(&big_numbers, 25);
+        assert_eq!(Some(BigUint::from(542529149u64)), invalid);
+    }
+
+    #[test]
+    fn parse_rejects_garbage_test() {
+        let r: Result<Vec<u64>> = parse("1\n2\nthree\n4");
+        let error = r.unwrap_err().to_string();
+        assert_eq!("Line 3: could not parse [three]", error);
+
+        let r: Result<Vec<u8>> = parse("255\n256");
+        assert!(r.is_err());
+    }
+
+    #[test]
+    fn overflow_test() {
+        // The pair sums and the running window sums overflow u64 here
+        let max = u64::MAX;
+        let v = vec![max - 1, 1, max, 2, max - 2, max];
+        let r: Vec<_> = invalid_numbers(&v, 2).collect();
+        assert_eq!(
+            vec![
+                InvalidNumber {
+                    position: 3,
+                    value: 2
+                },
+                InvalidNumber {
+                    position: 4,
+                    value: max - 2
+                }
+            ],
+            r
+        );
+
+        // Adding the 5 to the window overflows, so we have to shrink before we can add it
+        let v = vec![max, 5, max - 5];
+        assert_eq!(Some(1..3), find_contiguous_range_adding_to(&v, &max));
+        assert_eq!(
+            Some(max),
+            find_min_max_sums_in_first_window_adding_to(&v, &max)
+        );
+
+        // Doesn't overflow with a wider type
+        let v: Vec<u128> = vec![3, max as u128, max as u128];
+        let target = max as u128 * 2;
+        assert_eq!(Some(1..3), find_contiguous_range_adding_to(&v, &target));
     }
 }
@@ -1,10 +1,15 @@
 use anyhow::Result;
 use combine::lib::collections::HashMap;
 use itertools::Itertools;
+use num::bigint::RandBigInt;
+use num::{BigUint, One, ToPrimitive, Zero};
+use rand::Rng;
+use std::collections::BTreeSet;
+use std::fmt;
 
 const INPUT: &str = include_str!("../data/day_10_input");
 
-pub fn run() -> Result<()> {
+pub fn run(modulus: Option<u64>) -> Result<()> {
     println!("*** Day 10: Adapter Array ***");
     println!("Input: {}", INPUT);
     let numbers = parse(INPUT);
@@ -16,7 +21,24 @@ pub fn run() -> Result<()> {
         .map(|(one, three)| *one * three);
     println!("Solution 1: {:?}", solution_1);
 
-    println!("Solution 2: {:?}", count_viable_chains(&numbers));
+    println!("Solution 2: {:?}", count_viable_chains(&numbers)?);
+
+    if let Some(modulus) = modulus {
+        println!(
+            "Solution 2 mod {}: {:?}",
+            modulus,
+            count_viable_chains_mod(&numbers, 3, modulus)
+        );
+    }
+
+    let adapters = Adapters::new(&numbers, 3);
+
+    debug!("Chains by joltage:\n{}", adapters.explain());
+    println!("First chain: {:?}", adapters.chains().next());
+    println!(
+        "Random chain: {:?}",
+        adapters.sample_chains(1, &mut rand::thread_rng())
+    );
 
     Ok(())
 }
@@ -46,47 +68,206 @@ fn differences_between_consecutive_elements(s: &[usize]) -> HashMap<usize, isize
         })
 }
 
-fn count_viable_chains(adapters: &[usize]) -> usize {
-    let sorted_adapter_joltages: Vec<_> = adapters.iter().sorted().copied().collect();
-
-    if let Some(&last_adapter_joltage) = sorted_adapter_joltages.last() {
-        let times_joltage_appears_in_chains = sorted_adapter_joltages.into_iter().fold(
-            // Lookup that maps adapter joltage to number of times it can appear in a chain
-            // given the adapters that we have
-            HashMap::new(),
-            |mut times_joltage_appears_in_chains, adapter_joltage| {
-                if adapter_joltage <= 3 {
-                    *times_joltage_appears_in_chains
-                        .entry(adapter_joltage)
-                        .or_insert(0) += 1;
+fn count_viable_chains(adapters: &[usize]) -> Result<usize> {
+    let count = Adapters::new(adapters, 3).count();
+    count
+        .to_usize()
+        .ok_or_else(|| anyhow::anyhow!("{} chains is too many to fit in a usize", count))
+}
+
+// Same as the count of chains % modulus, without ever holding the full count: only a table of
+// counts modulo `modulus` gets built, so it stays cheap no matter how many adapters there are.
+fn count_viable_chains_mod(adapters: &[usize], max_step: usize, modulus: u64) -> Option<u64> {
+    if modulus == 0 {
+        return None;
+    }
+    let joltages = sorted_with_wall(adapters);
+    let len = joltages.len();
+    if len < 2 {
+        return Some(0);
+    }
+    let mut chains_from_wall = vec![0u64; len];
+    chains_from_wall[0] = 1 % modulus;
+    for idx in 1..len {
+        chains_from_wall[idx] = predecessors(&joltages, max_step, idx).fold(0, |acc, prev| {
+            ((acc as u128 + chains_from_wall[prev] as u128) % modulus as u128) as u64
+        });
+    }
+    Some(chains_from_wall[len - 1])
+}
+
+fn sorted_with_wall(adapters: &[usize]) -> Vec<usize> {
+    let mut joltages = vec![0];
+    joltages.extend(adapters.iter().sorted());
+    joltages
+}
+
+// Indices of the joltages that can plug into the one at idx
+fn predecessors(
+    joltages: &[usize],
+    max_step: usize,
+    idx: usize,
+) -> impl Iterator<Item = usize> + '_ {
+    let joltage = joltages[idx];
+    (0..idx)
+        .rev()
+        .take_while(move |prev| joltage - joltages[*prev] <= max_step)
+        .filter(move |prev| joltages[*prev] < joltage)
+}
+
+struct Adapters {
+    // Sorted, starting with the wall outlet's 0
+    joltages: Vec<usize>,
+    max_step: usize,
+    // For each joltage, how many chains go from the wall up to it, and how many go from it up to
+    // the highest adapter (which *must* be in every chain since the device is rated off it).
+    //
+    // These grow roughly like the tribonacci numbers, so they overflow any fixed width integer
+    // once there are more than a few hundred adapters.
+    chains_from_wall: Vec<BigUint>,
+    chains_to_end: Vec<BigUint>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+struct ChainTableRow {
+    joltage: usize,
+    chains: BigUint,
+    from: Vec<usize>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+struct ChainTable(Vec<ChainTableRow>);
+
+impl fmt::Display for ChainTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:>8} | {:>16} | from", "joltage", "chains")?;
+        for row in self.0.iter() {
+            let line = format!(
+                "{:>8} | {:>16} | {}",
+                row.joltage,
+                row.chains,
+                row.from.iter().join(" + ")
+            );
+            writeln!(f, "{}", line.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+impl Adapters {
+    fn new(adapters: &[usize], max_step: usize) -> Adapters {
+        let joltages = sorted_with_wall(adapters);
+        let len = joltages.len();
+
+        let mut adapters = Adapters {
+            joltages,
+            max_step,
+            chains_from_wall: vec![BigUint::zero(); len],
+            chains_to_end: vec![BigUint::zero(); len],
+        };
+
+        adapters.chains_from_wall[0] = BigUint::one();
+        for idx in 1..len {
+            adapters.chains_from_wall[idx] = adapters
+                .predecessors(idx)
+                .map(|prev| &adapters.chains_from_wall[prev])
+                .sum();
+        }
+
+        adapters.chains_to_end[len - 1] = BigUint::one();
+        for idx in (0..len - 1).rev() {
+            adapters.chains_to_end[idx] = adapters
+                .successors(idx)
+                .map(|next| &adapters.chains_to_end[next])
+                .sum();
+        }
+        adapters
+    }
+
+    fn predecessors(&self, idx: usize) -> impl Iterator<Item = usize> + '_ {
+        predecessors(&self.joltages, self.max_step, idx)
+    }
+
+    // Indices of the joltages that the one at idx can plug into
+    fn successors(&self, idx: usize) -> impl Iterator<Item = usize> + '_ {
+        let joltage = self.joltages[idx];
+        (idx + 1..self.joltages.len())
+            .take_while(move |next| self.joltages[*next] - joltage <= self.max_step)
+            .filter(move |next| self.joltages[*next] > joltage)
+    }
+
+    fn count(&self) -> BigUint {
+        if self.joltages.len() > 1 {
+            self.chains_to_end[0].clone()
+        } else {
+            BigUint::zero()
+        }
+    }
+
+    fn explain(&self) -> ChainTable {
+        let rows = self
+            .joltages
+            .iter()
+            .enumerate()
+            .map(|(idx, joltage)| ChainTableRow {
+                joltage: *joltage,
+                chains: self.chains_from_wall[idx].clone(),
+                from: self
+                    .predecessors(idx)
+                    .map(|prev| self.joltages[prev])
+                    .sorted()
+                    .collect(),
+            })
+            .collect();
+        ChainTable(rows)
+    }
+
+    // Chains are ordered by comparing their joltages, so the 0th one always takes the smallest
+    // step it can. We find the chain by skipping over every chain that goes through a smaller
+    // next adapter, which we know the count of without visiting them.
+    fn nth_chain(&self, n: &BigUint) -> Option<Vec<usize>> {
+        if *n >= self.count() {
+            return None;
+        }
+        let mut n = n.clone();
+        let last = self.joltages.len() - 1;
+        let mut chain = Vec::new();
+        let mut idx = 0;
+        while idx != last {
+            for next in self.successors(idx) {
+                let through_next = &self.chains_to_end[next];
+                if n < *through_next {
+                    idx = next;
+                    break;
+                } else {
+                    n -= through_next;
                 }
+            }
+            chain.push(self.joltages[idx]);
+        }
+        Some(chain)
+    }
 
-                (1..4.min(adapter_joltage)).into_iter().fold(
-                    times_joltage_appears_in_chains,
-                    |mut inner_times_joltage_appears_in_chains, joltage_decrease| {
-                        let lower_joltage = adapter_joltage - joltage_decrease;
-                        let times_lower_joltage_has_appeared =
-                            inner_times_joltage_appears_in_chains
-                                .get(&lower_joltage)
-                                .copied()
-                                .unwrap_or(0);
-                        *inner_times_joltage_appears_in_chains
-                            .entry(adapter_joltage)
-                            .or_insert(0) += times_lower_joltage_has_appeared;
-                        inner_times_joltage_appears_in_chains
-                    },
-                )
-            },
-        );
+    fn chains(&self) -> impl Iterator<Item = Vec<usize>> + '_ {
+        std::iter::successors(Some(BigUint::zero()), |n| Some(n + 1u32))
+            .map(move |n| self.nth_chain(&n))
+            .take_while(|chain| chain.is_some())
+            .flatten()
+    }
 
-        // the last adapter *must* be part of the chain because the device adapter joltage is
-        // based on it.
-        times_joltage_appears_in_chains
-            .get(&last_adapter_joltage)
-            .copied()
-            .unwrap_or(0)
-    } else {
-        0
+    // k distinct chains, each set of k equally likely. Fewer if there aren't k chains.
+    fn sample_chains<R: Rng>(&self, k: usize, rng: &mut R) -> Vec<Vec<usize>> {
+        let count = self.count();
+        if BigUint::from(k) >= count {
+            return self.chains().collect();
+        }
+        // Redraw on collisions; with k < count we always get there, and quickly unless k is
+        // close to count.
+        let mut ns = BTreeSet::new();
+        while ns.len() < k {
+            ns.insert(rng.gen_biguint_below(&count));
+        }
+        ns.iter().filter_map(|n| self.nth_chain(n)).collect()
     }
 }
 
@@ -153,7 +334,7 @@ mod tests {
     #[test]
     fn count_viable_chains_test_1() {
         let v = vec![16, 10, 15, 5, 1, 11, 7, 19, 6, 12, 4];
-        let r = count_viable_chains(&v);
+        let r = count_viable_chains(&v).unwrap();
         assert_eq!(8, r);
         // panic!()
     }
@@ -164,7 +345,151 @@ mod tests {
             28, 33, 18, 42, 31, 14, 46, 20, 48, 47, 24, 23, 49, 45, 19, 38, 39, 11, 1, 32, 25, 35,
             8, 17, 7, 9, 4, 2, 34, 10, 3,
         ];
-        let r = count_viable_chains(&v);
+        let r = count_viable_chains(&v).unwrap();
         assert_eq!(19208, r)
     }
+
+    #[test]
+    fn chains_test() {
+        let adapters = Adapters::new(&[16, 10, 15, 5, 1, 11, 7, 19, 6, 12, 4], 3);
+        let chains: Vec<_> = adapters.chains().collect();
+        assert_eq!(8, chains.len());
+        assert_eq!(vec![1, 4, 5, 6, 7, 10, 11, 12, 15, 16, 19], chains[0]);
+        assert_eq!(vec![1, 4, 7, 10, 12, 15, 16, 19], chains[7]);
+        // Sorted and distinct
+        assert!(chains.windows(2).all(|w| w[0] < w[1]));
+        for chain in chains {
+            assert_eq!(Some(&1), chain.first());
+            assert_eq!(Some(&19), chain.last());
+            assert!(chain.windows(2).all(|w| w[1] - w[0] <= 3));
+        }
+        assert_eq!(None, adapters.nth_chain(&BigUint::from(8u32)));
+    }
+
+    #[test]
+    fn sample_chains_test() {
+        let v = vec![
+            28, 33, 18, 42, 31, 14, 46, 20, 48, 47, 24, 23, 49, 45, 19, 38, 39, 11, 1, 32, 25, 35,
+            8, 17, 7, 9, 4, 2, 34, 10, 3,
+        ];
+        let adapters = Adapters::new(&v, 3);
+        let mut rng = rand::thread_rng();
+        let samples = adapters.sample_chains(5, &mut rng);
+        assert_eq!(5, samples.len());
+        assert_eq!(5, samples.iter().unique().count());
+        for chain in samples {
+            assert_eq!(Some(&49), chain.last());
+            assert!(chain.windows(2).all(|w| w[1] - w[0] <= 3));
+        }
+
+        let small = Adapters::new(&[1, 2], 3);
+        assert_eq!(2, small.sample_chains(10, &mut rng).len());
+    }
+
+    #[test]
+    fn max_step_test() {
+        let v = vec![16, 10, 15, 5, 1, 11, 7, 19, 6, 12, 4];
+        // 1 -> 4 is too big a step
+        assert_eq!(BigUint::zero(), Adapters::new(&v, 2).count());
+        assert_eq!(0, Adapters::new(&v, 2).chains().count());
+        let all_steps_of_1 = vec![1, 2, 3, 4, 5];
+        assert_eq!(BigUint::one(), Adapters::new(&all_steps_of_1, 1).count());
+        // 2^4 ways to pick from 1..4, but we can't skip all of 2, 3 and 4 with 3 steps
+        assert_eq!(
+            BigUint::from(13u32),
+            Adapters::new(&all_steps_of_1, 3).count()
+        );
+        assert_eq!(
+            BigUint::from(16u32),
+            Adapters::new(&all_steps_of_1, 5).count()
+        );
+    }
+
+    #[test]
+    fn explain_test() {
+        let table = Adapters::new(&[1, 4, 5, 6, 7], 3).explain();
+        let expected = ChainTable(vec![
+            ChainTableRow {
+                joltage: 0,
+                chains: BigUint::from(1u32),
+                from: vec![],
+            },
+            ChainTableRow {
+                joltage: 1,
+                chains: BigUint::from(1u32),
+                from: vec![0],
+            },
+            ChainTableRow {
+                joltage: 4,
+                chains: BigUint::from(1u32),
+                from: vec![1],
+            },
+            ChainTableRow {
+                joltage: 5,
+                chains: BigUint::from(1u32),
+                from: vec![4],
+            },
+            ChainTableRow {
+                joltage: 6,
+                chains: BigUint::from(2u32),
+                from: vec![4, 5],
+            },
+            ChainTableRow {
+                joltage: 7,
+                chains: BigUint::from(4u32),
+                from: vec![4, 5, 6],
+            },
+        ]);
+        assert_eq!(expected, table);
+        assert_eq!(
+            "
+ joltage |           chains | from
+       0 |                1 |
+       1 |                1 | 0
+       4 |                1 | 1
+       5 |                1 | 4
+       6 |                2 | 4 + 5
+       7 |                4 | 4 + 5 + 6
+",
+            format!("\n{}", table)
+        );
+    }
+
+    #[test]
+    fn big_counts_test() {
+        // 1, 2, 3, ... gives the tribonacci numbers
+        let adapters: Vec<_> = (1..=10_000).collect();
+        let big = Adapters::new(&adapters, 3);
+        let count = big.count();
+        assert!(count.bits() > 8000);
+        assert!(count_viable_chains(&adapters).is_err());
+
+        let modulus = 1_000_000_007u64;
+        let expected = (&count % modulus).to_u64();
+        assert_eq!(expected, count_viable_chains_mod(&adapters, 3, modulus));
+        assert_eq!(Some(0), count_viable_chains_mod(&adapters, 3, 1));
+        assert_eq!(None, count_viable_chains_mod(&adapters, 3, 0));
+
+        let mut rng = rand::thread_rng();
+        let samples = big.sample_chains(3, &mut rng);
+        assert_eq!(3, samples.len());
+        for chain in samples {
+            assert_eq!(Some(&10_000), chain.last());
+            assert!(chain.windows(2).all(|w| w[1] - w[0] <= 3));
+        }
+        // The very last chain jumps by 3 whenever it can
+        let last = big.nth_chain(&(count - 1u32)).unwrap();
+        assert_eq!(vec![3, 6, 9], last[..3].to_vec());
+    }
+
+    #[test]
+    fn count_mod_test() {
+        let v = vec![
+            28, 33, 18, 42, 31, 14, 46, 20, 48, 47, 24, 23, 49, 45, 19, 38, 39, 11, 1, 32, 25, 35,
+            8, 17, 7, 9, 4, 2, 34, 10, 3,
+        ];
+        assert_eq!(Some(19208 % 1000), count_viable_chains_mod(&v, 3, 1000));
+        assert_eq!(Some(19208), count_viable_chains_mod(&v, 3, u64::MAX));
+        assert_eq!(Some(0), count_viable_chains_mod(&[], 3, 1000));
+    }
 }
@@ -3,28 +3,100 @@ use combine::easy;
 use combine::lib::fmt::Formatter;
 use combine::parser::char::*;
 use combine::*;
+use std::io::{self, Write};
 use std::result::Result as StdResult;
+use std::thread;
+use std::time::Duration;
+
+use automaton::{Grid, Neighbourhood, RuleSet, Simulation, Transition};
 
 const INPUT: &str = include_str!("../data/day_11_input");
 
+pub mod automaton;
+
 pub fn run() -> Result<()> {
     println!("*** Day 11: Seating System ***");
     println!("Input: {}", INPUT);
     let grid = parse(INPUT)?;
 
-    let mut simulation = Simulation::new(grid.clone());
-    simulation.run_til_no_changes(|s| s.step(4, |grid, i, j| grid.adjacent_grid_results(i, j)));
-    println!("Solution 1: {:?}", simulation.occupied_seats());
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let mut simulation = Simulation::new(grid.clone(), seating_rules(4, Neighbourhood::Moore))
+        .with_max_steps(MAX_STEPS)
+        .with_threads(threads);
+    let outcome = simulation.run();
+    println!("Outcome 1: {:?}", outcome);
+    println!("Solution 1: {:?}", simulation.count(Space::Occupied));
+
+    let mut simulation_tolerant = Simulation::new(grid, seating_rules(5, line_of_sight()))
+        .with_max_steps(MAX_STEPS)
+        .with_threads(threads);
+    let outcome_tolerant = simulation_tolerant.run();
+    println!("Outcome 2: {:?}", outcome_tolerant);
+    println!(
+        "Solution 2: {:?}",
+        simulation_tolerant.count(Space::Occupied)
+    );
 
-    let mut simulation_tolerant = Simulation::new(grid);
-    simulation_tolerant
-        .run_til_no_changes(|s| s.step(5, |grid, i, j| grid.next_adjacent_seat_results(i, j)));
-    println!("Solution 2: {:?}", simulation_tolerant.occupied_seats());
+    Ok(())
+}
 
+// Plays both simulations out in the terminal, one generation per frame
+pub fn animate(frame_delay: Duration) -> Result<()> {
+    let grid = parse(INPUT)?;
+    let simulations = vec![
+        (
+            "Day 11: Seating System (part 1)",
+            Simulation::new(grid.clone(), seating_rules(4, Neighbourhood::Moore)),
+        ),
+        (
+            "Day 11: Seating System (part 2)",
+            Simulation::new(grid, seating_rules(5, line_of_sight())),
+        ),
+    ];
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    for (title, simulation) in simulations {
+        let mut simulation = simulation.with_max_steps(MAX_STEPS);
+        let mut result = Ok(());
+        let outcome = simulation.run_with(|generation, grid| {
+            if result.is_ok() {
+                result = render_frame(&mut out, title, generation, grid);
+                thread::sleep(frame_delay);
+            }
+        });
+        result?;
+        writeln!(
+            out,
+            "{:?}, {} seats occupied",
+            outcome,
+            simulation.count(Space::Occupied)
+        )?;
+        thread::sleep(frame_delay * 10);
+    }
     Ok(())
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+// Both parts settle in under a hundred steps, so this is only here in case the rules get changed
+// into something that never does
+const MAX_STEPS: usize = 10_000;
+
+const CLEAR_SCREEN: &str = "\x1b[2J\x1b[H";
+
+fn render_frame<W: Write>(
+    out: &mut W,
+    title: &str,
+    generation: usize,
+    grid: &Grid<Space>,
+) -> Result<()> {
+    write!(out, "{}", CLEAR_SCREEN)?;
+    writeln!(out, "*** {} ***", title)?;
+    writeln!(out, "Generation {}", generation)?;
+    write!(out, "{}", grid)?;
+    out.flush()?;
+    Ok(())
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
 enum Space {
     Occupied,
     Floor,
@@ -42,183 +114,36 @@ impl std::fmt::Display for Space {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
-struct Grid(Vec<Vec<Space>>);
-
-impl std::fmt::Display for Grid {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        for row in self.0.iter() {
-            for space in row {
-                write!(f, "{}", space)?
-            }
-            writeln!(f)?
-        }
-        Ok(())
+// Seats become occupied when nothing around them is, and empty when too much around them is.
+// Floor never changes.
+fn seating_rules(max_occupied_seats: usize, neighbourhood: Neighbourhood<Space>) -> RuleSet<Space> {
+    RuleSet {
+        transitions: vec![
+            Transition {
+                from: Space::EmptySeat,
+                counting: Space::Occupied,
+                when: 0..=0,
+                to: Space::Occupied,
+            },
+            Transition {
+                from: Space::Occupied,
+                counting: Space::Occupied,
+                when: max_occupied_seats..=usize::MAX,
+                to: Space::EmptySeat,
+            },
+        ],
+        neighbourhood,
     }
 }
 
-impl Grid {
-    // Non-wrap
-    fn adjacent_grid_results(&self, i: usize, j: usize) -> Vec<&Space> {
-        let north = if 0 == i {
-            None
-        } else {
-            self.0.get(i - 1).and_then(|north_row| north_row.get(j))
-        };
-        let north_east = if 0 == i {
-            None
-        } else {
-            self.0.get(i - 1).and_then(|north_row| north_row.get(j + 1))
-        };
-        let east = self.0.get(i).and_then(|current_row| current_row.get(j + 1));
-
-        let south_east = self.0.get(i + 1).and_then(|south_row| south_row.get(j + 1));
-        let south = self.0.get(i + 1).and_then(|south_row| south_row.get(j));
-
-        let south_west = if j == 0 {
-            None
-        } else {
-            self.0.get(i + 1).and_then(|south_row| south_row.get(j - 1))
-        };
-
-        let west = if j == 0 {
-            None
-        } else {
-            self.0.get(i).and_then(|south_row| south_row.get(j - 1))
-        };
-
-        let north_west = if i == 0 || j == 0 {
-            None
-        } else {
-            self.0.get(i - 1).and_then(|south_row| south_row.get(j - 1))
-        };
-
-        vec![
-            north, north_east, east, south_east, south, south_west, west, north_west,
-        ]
-        .into_iter()
-        .flatten()
-        .collect()
-    }
-
-    // Non-wrap
-    fn next_adjacent_seat_results(&self, i: usize, j: usize) -> Vec<&Space> {
-        let north = if 0 == i {
-            None
-        } else {
-            (0..i)
-                .rev()
-                .filter_map(|row_idx| {
-                    self.0
-                        .get(row_idx)
-                        .and_then(|row| row.get(j))
-                        .filter(|space| **space != Space::Floor)
-                })
-                .next()
-        };
-        let north_east = if 0 == i {
-            None
-        } else {
-            (0..i)
-                .rev()
-                .enumerate()
-                .filter_map(|(step_idx, row_idx)| {
-                    let col = j + step_idx + 1;
-                    self.0
-                        .get(row_idx)
-                        .and_then(|row| row.get(col))
-                        .filter(|space| **space != Space::Floor)
-                })
-                .next()
-        };
-        let east = {
-            self.0.get(i).and_then(|current_row| {
-                current_row
-                    .iter()
-                    .skip(j + 1)
-                    .find(|space| **space != Space::Floor)
-            })
-        };
-
-        let south_east = {
-            self.0
-                .iter()
-                .skip(i + 1)
-                .enumerate()
-                .filter_map(|(step_idx, row)| {
-                    row.get(j + step_idx + 1)
-                        .filter(|space| **space != Space::Floor)
-                })
-                .next()
-        };
-
-        let south = {
-            self.0
-                .iter()
-                .skip(i + 1)
-                .filter_map(|row| row.get(j).filter(|space| **space != Space::Floor))
-                .next()
-        };
-
-        let south_west = if j == 0 {
-            None
-        } else {
-            self.0
-                .iter()
-                .skip(i + 1)
-                .zip((0..j).rev())
-                .filter_map(|(south_row, col)| {
-                    south_row.get(col).filter(|space| **space != Space::Floor)
-                })
-                .next()
-        };
-
-        let west = if j == 0 {
-            None
-        } else {
-            self.0.get(i).and_then(|row| {
-                row.iter()
-                    .take(j)
-                    .rev()
-                    .find(|space| **space != Space::Floor)
-            })
-        };
-
-        let north_west = if i == 0 || j == 0 {
-            None
-        } else {
-            (0..i)
-                .rev()
-                .zip((0..j).rev())
-                .filter_map(|(row_idx, col_idx)| {
-                    self.0
-                        .get(row_idx)
-                        .and_then(|row| row.get(col_idx))
-                        .filter(|space| **space != Space::Floor)
-                })
-                .next()
-        };
-
-        let before_flattening = vec![
-            north, north_east, east, south_east, south, south_west, west, north_west,
-        ];
-        before_flattening.into_iter().flatten().collect()
-    }
-
-    fn occupied_seats(&self) -> usize {
-        self.0.iter().fold(0, |acc, row| {
-            row.iter().fold(acc, |inner_acc, space| {
-                if *space == Space::Occupied {
-                    inner_acc + 1
-                } else {
-                    inner_acc
-                }
-            })
-        })
+// The first seat in each direction, looking over the floor
+fn line_of_sight() -> Neighbourhood<Space> {
+    Neighbourhood::LineOfSight {
+        transparent: Space::Floor,
     }
 }
 
-fn parse(s: &str) -> StdResult<Grid, easy::ParseError<&str>> {
+fn parse(s: &str) -> StdResult<Grid<Space>, easy::ParseError<&str>> {
     let empty_seat_parser = char('L').map(|_| Space::EmptySeat);
     let occupied_parser = char('#').map(|_| Space::Occupied);
     let floor_parser = char('.').map(|_| Space::Floor);
@@ -229,70 +154,9 @@ fn parse(s: &str) -> StdResult<Grid, easy::ParseError<&str>> {
     Ok(r)
 }
 
-struct Simulation {
-    current: Grid,
-    next: Grid,
-    no_changes: bool,
-}
-
-impl Simulation {
-    fn occupied_seats(&self) -> usize {
-        self.current.occupied_seats()
-    }
-
-    fn new(grid: Grid) -> Simulation {
-        Simulation {
-            current: grid.clone(),
-            next: grid,
-            no_changes: false,
-        }
-    }
-    //
-    // fn step(&mut self) {
-    //     self.step2(|grid, i, j| grid.adjacent_grid_results(i, j))
-    // }
-    //
-    // fn step_tolerant(&mut self) {
-    //     self.step2(|grid, i, j| grid.next_adjacent_seat_results(i, j))
-    // }
-
-    fn step<F>(&mut self, max_occupied_seats: usize, adjacent_search: F)
-    where
-        F: Fn(&Grid, usize, usize) -> Vec<&Space>,
-    {
-        for (i, row) in self.current.0.iter().enumerate() {
-            for (j, space) in row.iter().enumerate() {
-                if *space != Space::Floor {
-                    let adjacent_occupied_seats = adjacent_search(&self.current, i, j)
-                        .iter()
-                        .filter(|s| ***s == Space::Occupied)
-                        .count();
-                    if *space == Space::EmptySeat && adjacent_occupied_seats == 0 {
-                        self.next.0[i][j] = Space::Occupied
-                    } else if *space == Space::Occupied
-                        && adjacent_occupied_seats >= max_occupied_seats
-                    {
-                        self.next.0[i][j] = Space::EmptySeat
-                    }
-                }
-            }
-        }
-        self.no_changes = self.current == self.next;
-        self.current = self.next.clone();
-    }
-
-    fn run_til_no_changes<F>(&mut self, step: F)
-    where
-        F: Fn(&mut Self),
-    {
-        while !self.no_changes {
-            step(self);
-        }
-    }
-}
-
 #[cfg(test)]
 mod tests {
+    use super::automaton::Outcome;
     use super::*;
     use Space::*;
 
@@ -334,18 +198,18 @@ LLLLLLL.LL
 
         assert_eq!(
             vec![&Floor, &EmptySeat, &EmptySeat],
-            grid.adjacent_grid_results(0, 0)
+            grid.neighbour_states(0, 0, &Neighbourhood::Moore)
         );
         assert_eq!(
             vec![
                 &EmptySeat, &Floor, &EmptySeat, &EmptySeat, &Floor, &EmptySeat, &EmptySeat,
                 &EmptySeat
             ],
-            grid.adjacent_grid_results(1, 3)
+            grid.neighbour_states(1, 3, &Neighbourhood::Moore)
         );
         assert_eq!(
             vec![&EmptySeat, &Floor, &EmptySeat,],
-            grid.adjacent_grid_results(2, 9)
+            grid.neighbour_states(2, 9, &Neighbourhood::Moore)
         );
     }
 
@@ -362,9 +226,12 @@ LLLLLLLLLL
 L.LLLLLL.L
 L.LLLLL.LL
 ";
-        let mut grid = Simulation::new(parse(input).unwrap());
-        grid.run_til_no_changes(|s| s.step(4, |grid, i, j| grid.adjacent_grid_results(i, j)));
-        assert_eq!(37, grid.occupied_seats());
+        let mut grid = Simulation::new(
+            parse(input).unwrap(),
+            seating_rules(4, Neighbourhood::Moore),
+        );
+        assert_eq!(Outcome::Stabilised { generation: 5 }, grid.run());
+        assert_eq!(37, grid.count(Occupied));
     }
 
     #[test]
@@ -380,7 +247,7 @@ L.LLLLL.LL
 ...#.....
 ";
         let parsed = parse(input).unwrap();
-        let r = parsed.next_adjacent_seat_results(4, 3);
+        let r = parsed.neighbour_states(4, 3, &line_of_sight());
         let expected = vec![
             &Occupied, &Occupied, &Occupied, &Occupied, &Occupied, &Occupied, &Occupied, &Occupied,
         ];
@@ -394,7 +261,7 @@ L.LLLLL.LL
 .............
 ";
         let parsed = parse(input).unwrap();
-        let r = parsed.next_adjacent_seat_results(1, 1);
+        let r = parsed.neighbour_states(1, 1, &line_of_sight());
         let expected = vec![&EmptySeat];
         assert_eq!(expected, r)
     }
@@ -409,7 +276,7 @@ L.LLLLL.LL
 .##.##.
 ";
         let parsed = parse(input).unwrap();
-        let r = parsed.next_adjacent_seat_results(3, 3);
+        let r = parsed.neighbour_states(3, 3, &line_of_sight());
         let expected: Vec<&Space> = vec![];
         assert_eq!(expected, r)
     }
@@ -427,8 +294,20 @@ LLLLLLLLLL
 L.LLLLLL.L
 L.LLLLL.LL
 ";
-        let mut grid = Simulation::new(parse(input).unwrap());
-        grid.run_til_no_changes(|s| s.step(5, |grid, i, j| grid.next_adjacent_seat_results(i, j)));
-        assert_eq!(26, grid.occupied_seats());
+        let mut grid = Simulation::new(parse(input).unwrap(), seating_rules(5, line_of_sight()));
+        assert_eq!(Outcome::Stabilised { generation: 6 }, grid.run());
+        assert_eq!(26, grid.count(Occupied));
+    }
+
+    #[test]
+    fn render_frame_test() {
+        let grid = parse("L.#\n##L\n").unwrap();
+        let mut out = Vec::new();
+        render_frame(&mut out, "Seats", 3, &grid).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(
+            format!("{}*** Seats ***\nGeneration 3\nL.#\n##L\n", CLEAR_SCREEN),
+            out
+        );
     }
 }
@@ -0,0 +1,887 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::RangeInclusive;
+
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct Grid<S>(pub Vec<Vec<S>>);
+
+impl<S: fmt::Display> fmt::Display for Grid<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for row in self.0.iter() {
+            for cell in row {
+                write!(f, "{}", cell)?
+            }
+            writeln!(f)?
+        }
+        Ok(())
+    }
+}
+
+// N, NE, E, SE, S, SW, W, NW
+const MOORE: [(isize, isize); 8] = [
+    (-1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+    (1, 0),
+    (1, -1),
+    (0, -1),
+    (-1, -1),
+];
+
+// N, E, S, W
+const VON_NEUMANN: [(isize, isize); 4] = [(-1, 0), (0, 1), (1, 0), (0, -1)];
+
+// Which cells count as neighbours. None of these wrap around the edges of the grid.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Neighbourhood<S> {
+    Moore,
+    VonNeumann,
+    // The first cell in each of the 8 Moore directions that isn't `transparent`
+    LineOfSight { transparent: S },
+    Offsets(Vec<(isize, isize)>),
+}
+
+// A cell in state `from` becomes `to` if the number of its neighbours in state `counting` is in
+// `when`
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Transition<S> {
+    pub from: S,
+    pub counting: S,
+    pub when: RangeInclusive<usize>,
+    pub to: S,
+}
+
+impl<S: Copy> Transition<S> {
+    pub fn always(from: S, to: S) -> Transition<S> {
+        Transition {
+            from,
+            counting: from,
+            when: 0..=usize::MAX,
+            to,
+        }
+    }
+}
+
+// The first transition that applies wins; cells that no transition applies to stay as they are
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct RuleSet<S> {
+    pub transitions: Vec<Transition<S>>,
+    pub neighbourhood: Neighbourhood<S>,
+}
+
+impl<S: Copy + Eq> RuleSet<S> {
+    pub fn next_state<'a, I>(&self, current: S, neighbours: I) -> S
+    where
+        I: Iterator<Item = &'a S> + Clone,
+        S: 'a,
+    {
+        self.transitions
+            .iter()
+            .filter(|t| t.from == current)
+            .find(|t| {
+                let count = neighbours.clone().filter(|n| **n == t.counting).count();
+                t.when.contains(&count)
+            })
+            .map(|t| t.to)
+            .unwrap_or(current)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum LifeCell {
+    Dead,
+    Alive,
+}
+
+impl fmt::Display for LifeCell {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LifeCell::Dead => write!(f, "."),
+            LifeCell::Alive => write!(f, "#"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum BrainCell {
+    Off,
+    On,
+    Dying,
+}
+
+impl fmt::Display for BrainCell {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BrainCell::Off => write!(f, "."),
+            BrainCell::On => write!(f, "#"),
+            BrainCell::Dying => write!(f, "o"),
+        }
+    }
+}
+
+impl RuleSet<LifeCell> {
+    // B3/S23
+    pub fn life() -> RuleSet<LifeCell> {
+        use LifeCell::*;
+        RuleSet {
+            transitions: vec![
+                Transition {
+                    from: Dead,
+                    counting: Alive,
+                    when: 3..=3,
+                    to: Alive,
+                },
+                Transition {
+                    from: Alive,
+                    counting: Alive,
+                    when: 2..=3,
+                    to: Alive,
+                },
+                Transition::always(Alive, Dead),
+            ],
+            neighbourhood: Neighbourhood::Moore,
+        }
+    }
+}
+
+impl RuleSet<BrainCell> {
+    pub fn brians_brain() -> RuleSet<BrainCell> {
+        use BrainCell::*;
+        RuleSet {
+            transitions: vec![
+                Transition {
+                    from: Off,
+                    counting: On,
+                    when: 2..=2,
+                    to: On,
+                },
+                Transition::always(On, Dying),
+                Transition::always(Dying, Off),
+            ],
+            neighbourhood: Neighbourhood::Moore,
+        }
+    }
+}
+
+impl<S: Copy + Eq> Grid<S> {
+    pub fn get(&self, i: usize, j: usize) -> Option<&S> {
+        self.0.get(i).and_then(|row| row.get(j))
+    }
+
+    pub fn count(&self, state: S) -> usize {
+        self.0
+            .iter()
+            .map(|row| row.iter().filter(|cell| **cell == state).count())
+            .sum()
+    }
+
+    fn offset(&self, i: usize, j: usize, (di, dj): (isize, isize)) -> Option<(usize, usize)> {
+        let i = i as isize + di;
+        let j = j as isize + dj;
+        if i < 0 || j < 0 {
+            None
+        } else {
+            self.get(i as usize, j as usize)
+                .map(|_| (i as usize, j as usize))
+        }
+    }
+
+    pub fn neighbours(
+        &self,
+        i: usize,
+        j: usize,
+        neighbourhood: &Neighbourhood<S>,
+    ) -> Vec<(usize, usize)> {
+        match neighbourhood {
+            Neighbourhood::Moore => self.neighbours_at(i, j, &MOORE),
+            Neighbourhood::VonNeumann => self.neighbours_at(i, j, &VON_NEUMANN),
+            Neighbourhood::Offsets(offsets) => self.neighbours_at(i, j, offsets),
+            Neighbourhood::LineOfSight { transparent } => MOORE
+                .iter()
+                .filter_map(|direction| {
+                    let mut current = self.offset(i, j, *direction)?;
+                    while self.get(current.0, current.1) == Some(transparent) {
+                        current = self.offset(current.0, current.1, *direction)?;
+                    }
+                    Some(current)
+                })
+                .collect(),
+        }
+    }
+
+    pub fn neighbour_states(
+        &self,
+        i: usize,
+        j: usize,
+        neighbourhood: &Neighbourhood<S>,
+    ) -> Vec<&S> {
+        self.neighbours(i, j, neighbourhood)
+            .into_iter()
+            .filter_map(|(i, j)| self.get(i, j))
+            .collect()
+    }
+
+    fn neighbours_at(&self, i: usize, j: usize, offsets: &[(isize, isize)]) -> Vec<(usize, usize)> {
+        offsets
+            .iter()
+            .filter_map(|offset| self.offset(i, j, *offset))
+            .collect()
+    }
+}
+
+// Every cell's neighbour list, worked out once and flattened into a single Vec (compressed sparse
+// row): the neighbours of cell `c` are `neighbours[offsets[c]..offsets[c + 1]]`. Cells are numbered
+// row by row. `watchers` is the same thing the other way round: the cells that have `c` as a
+// neighbour, which are the ones to look at again when `c` changes.
+//
+// Line of sight is resolved against the grid we're built from, so it stays right as long as
+// transparent cells never change (floor in the seating rules never does).
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Adjacency {
+    positions: Vec<(usize, usize)>,
+    offsets: Vec<usize>,
+    neighbours: Vec<usize>,
+    watcher_offsets: Vec<usize>,
+    watchers: Vec<usize>,
+}
+
+impl Adjacency {
+    pub fn new<S: Copy + Eq>(grid: &Grid<S>, neighbourhood: &Neighbourhood<S>) -> Adjacency {
+        let mut row_starts = Vec::with_capacity(grid.0.len());
+        let mut positions = Vec::new();
+        for (i, row) in grid.0.iter().enumerate() {
+            row_starts.push(positions.len());
+            positions.extend((0..row.len()).map(|j| (i, j)));
+        }
+
+        let mut offsets = Vec::with_capacity(positions.len() + 1);
+        let mut neighbours = Vec::new();
+        offsets.push(0);
+        for (i, j) in positions.iter() {
+            neighbours.extend(
+                grid.neighbours(*i, *j, neighbourhood)
+                    .into_iter()
+                    .map(|(ni, nj)| row_starts[ni] + nj),
+            );
+            offsets.push(neighbours.len());
+        }
+
+        // Transpose by counting how many cells watch each cell, then filling in the slots
+        let mut watcher_offsets = vec![0; positions.len() + 1];
+        for n in neighbours.iter() {
+            watcher_offsets[n + 1] += 1;
+        }
+        for c in 0..positions.len() {
+            watcher_offsets[c + 1] += watcher_offsets[c];
+        }
+        let mut next_slot = watcher_offsets.clone();
+        let mut watchers = vec![0; neighbours.len()];
+        for c in 0..positions.len() {
+            for n in neighbours[offsets[c]..offsets[c + 1]].iter() {
+                watchers[next_slot[*n]] = c;
+                next_slot[*n] += 1;
+            }
+        }
+
+        Adjacency {
+            positions,
+            offsets,
+            neighbours,
+            watcher_offsets,
+            watchers,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    pub fn position(&self, cell: usize) -> (usize, usize) {
+        self.positions[cell]
+    }
+
+    pub fn neighbours(&self, cell: usize) -> &[usize] {
+        &self.neighbours[self.offsets[cell]..self.offsets[cell + 1]]
+    }
+
+    pub fn watchers(&self, cell: usize) -> &[usize] {
+        &self.watchers[self.watcher_offsets[cell]..self.watcher_offsets[cell + 1]]
+    }
+}
+
+// Only cells that might change are looked at on each step: to start with that's all of them, and
+// after that it's the cells that changed (they may keep changing on their own, like Brian's Brain)
+// and the ones watching them.
+//
+// The grid is double buffered: a step writes into `next` and then swaps it with `current`. After
+// the swap `next` is a generation behind only in the cells that just changed, and those are always
+// in the next worklist, so writing every cell we evaluate is enough to bring it up to date.
+pub struct Simulation<S> {
+    current: Grid<S>,
+    next: Grid<S>,
+    rules: RuleSet<S>,
+    adjacency: Adjacency,
+    dirty: Vec<usize>,
+    queued: Vec<bool>,
+    // Scratch space kept between steps so stepping doesn't allocate: one buffer of evaluated cells
+    // per thread, and the cells that changed
+    evaluated: Vec<Vec<(usize, S)>>,
+    changed: Vec<usize>,
+    generation: usize,
+    max_steps: Option<usize>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Outcome {
+    // The grid at `generation` is the same as the one after it
+    Stabilised { generation: usize },
+    // The grid at `start` comes round again every `period` steps (always more than 1, since a
+    // period of 1 is stabilising)
+    Cycled { start: usize, period: usize },
+    StepLimit { steps: usize },
+}
+
+// Below this many cells to look at, starting threads costs more than it saves
+const MIN_CELLS_PER_THREAD: usize = 1024;
+
+impl<S: Copy + Eq + Send + Sync> Simulation<S> {
+    pub fn new(grid: Grid<S>, rules: RuleSet<S>) -> Simulation<S> {
+        let adjacency = Adjacency::new(&grid, &rules.neighbourhood);
+        let cells = adjacency.len();
+        Simulation {
+            current: grid.clone(),
+            next: grid,
+            rules,
+            adjacency,
+            dirty: (0..cells).collect(),
+            queued: vec![true; cells],
+            evaluated: vec![Vec::with_capacity(cells)],
+            changed: Vec::with_capacity(cells),
+            generation: 0,
+            max_steps: None,
+        }
+    }
+
+    pub fn with_max_steps(mut self, max_steps: usize) -> Simulation<S> {
+        self.max_steps = Some(max_steps);
+        self
+    }
+
+    // Evaluate cells on up to this many threads when there are enough of them to be worth it
+    pub fn with_threads(mut self, threads: usize) -> Simulation<S> {
+        self.evaluated.resize_with(threads.max(1), Vec::new);
+        self
+    }
+
+    // How many steps have been taken so far
+    pub fn generation(&self) -> usize {
+        self.generation
+    }
+
+    pub fn grid(&self) -> &Grid<S> {
+        &self.current
+    }
+
+    pub fn count(&self, state: S) -> usize {
+        self.current.count(state)
+    }
+
+    // How many cells the next step will look at
+    pub fn pending(&self) -> usize {
+        self.dirty.len()
+    }
+
+    // How many cells changed on the last step
+    pub fn changes(&self) -> usize {
+        self.changed.len()
+    }
+
+    pub fn step(&mut self) {
+        self.evaluate();
+        self.changed.clear();
+        for evaluated in self.evaluated.iter() {
+            for (cell, state) in evaluated.iter() {
+                let (i, j) = self.adjacency.position(*cell);
+                if self.current.0[i][j] != *state {
+                    self.changed.push(*cell);
+                }
+                self.next.0[i][j] = *state;
+            }
+        }
+        std::mem::swap(&mut self.current, &mut self.next);
+
+        for cell in self.dirty.drain(..) {
+            self.queued[cell] = false;
+        }
+        for cell in self.changed.iter() {
+            let affected = std::iter::once(cell).chain(self.adjacency.watchers(*cell));
+            for affected in affected {
+                if !self.queued[*affected] {
+                    self.queued[*affected] = true;
+                    self.dirty.push(*affected);
+                }
+            }
+        }
+        self.generation += 1;
+    }
+
+    // Works out the next state of every dirty cell into `evaluated`, splitting the worklist
+    // between threads if there's enough of it
+    fn evaluate(&mut self) {
+        let threads = self
+            .evaluated
+            .len()
+            .min(self.dirty.len() / MIN_CELLS_PER_THREAD)
+            .max(1);
+        let chunk_size = self.dirty.len().div_ceil(threads);
+        let grid = &self.current;
+        let rules = &self.rules;
+        let adjacency = &self.adjacency;
+        let dirty = &self.dirty;
+        let evaluated = &mut self.evaluated;
+        let evaluate_chunk = |cells: &[usize], into: &mut Vec<(usize, S)>| {
+            into.clear();
+            into.extend(cells.iter().map(|cell| {
+                let (i, j) = adjacency.position(*cell);
+                let neighbours = adjacency.neighbours(*cell).iter().map(|n| {
+                    let (ni, nj) = adjacency.position(*n);
+                    &grid.0[ni][nj]
+                });
+                (*cell, rules.next_state(grid.0[i][j], neighbours))
+            }));
+        };
+
+        for unused in evaluated[threads..].iter_mut() {
+            unused.clear();
+        }
+        if threads == 1 {
+            evaluate_chunk(dirty, &mut evaluated[0]);
+        } else {
+            std::thread::scope(|scope| {
+                for (cells, into) in dirty.chunks(chunk_size).zip(evaluated.iter_mut()) {
+                    scope.spawn(move || evaluate_chunk(cells, into));
+                }
+            });
+        }
+    }
+}
+
+impl<S: Copy + Eq + Hash + Send + Sync> Simulation<S> {
+    pub fn run(&mut self) -> Outcome {
+        self.run_with(|_, _| {})
+    }
+
+    // Runs until the grid stops changing, starts repeating itself, or we run out of steps, calling
+    // `on_generation` with every distinct grid along the way (including the one we start with).
+    //
+    // Only a hash of each generation is kept. When a hash comes round again, the earlier grid is
+    // rebuilt by replaying from the one we started with, so a collision can't be taken for a
+    // cycle.
+    pub fn run_with<F>(&mut self, mut on_generation: F) -> Outcome
+    where
+        F: FnMut(usize, &Grid<S>),
+    {
+        let first_generation = self.generation;
+        let first = self.current.clone();
+        let mut seen: HashMap<u64, Vec<usize>> = HashMap::new();
+        loop {
+            let earlier = seen.entry(grid_hash(&self.current)).or_default();
+            let start = earlier.iter().copied().find(|generation| {
+                self.replay(&first, first_generation, *generation) == self.current
+            });
+            if let Some(start) = start {
+                return Outcome::Cycled {
+                    start,
+                    period: self.generation - start,
+                };
+            }
+            earlier.push(self.generation);
+            on_generation(self.generation, &self.current);
+            if let Some(max_steps) = self.max_steps {
+                if self.generation >= max_steps {
+                    return Outcome::StepLimit {
+                        steps: self.generation,
+                    };
+                }
+            }
+            self.step();
+            if self.changes() == 0 {
+                return Outcome::Stabilised {
+                    generation: self.generation - 1,
+                };
+            }
+        }
+    }
+
+    // The grid at `generation`, stepping on from `grid` at `from`
+    fn replay(&self, grid: &Grid<S>, from: usize, generation: usize) -> Grid<S> {
+        let mut replay = Simulation::new(grid.clone(), self.rules.clone());
+        for _ in from..generation {
+            replay.step();
+        }
+        replay.current
+    }
+}
+
+fn grid_hash<S: Hash>(grid: &Grid<S>) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    grid.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid<S>(s: &str, state: impl Fn(char) -> S) -> Grid<S> {
+        Grid(
+            s.lines()
+                .map(|line| line.chars().map(&state).collect())
+                .collect(),
+        )
+    }
+
+    fn life(s: &str) -> Grid<LifeCell> {
+        grid(s, |c| match c {
+            '#' => LifeCell::Alive,
+            _ => LifeCell::Dead,
+        })
+    }
+
+    fn brain(s: &str) -> Grid<BrainCell> {
+        grid(s, |c| match c {
+            '#' => BrainCell::On,
+            'o' => BrainCell::Dying,
+            _ => BrainCell::Off,
+        })
+    }
+
+    #[test]
+    fn neighbours_test() {
+        let g = life(
+            "#..
+.#.
+..#
+",
+        );
+        assert_eq!(
+            vec![(0, 1), (1, 1), (1, 0)],
+            g.neighbours(0, 0, &Neighbourhood::Moore)
+        );
+        assert_eq!(
+            vec![(0, 1), (1, 2), (2, 1), (1, 0)],
+            g.neighbours(1, 1, &Neighbourhood::VonNeumann)
+        );
+        assert_eq!(
+            vec![(2, 2)],
+            g.neighbours(0, 0, &Neighbourhood::Offsets(vec![(2, 2), (3, 3), (-1, 0)]))
+        );
+        let line_of_sight = Neighbourhood::LineOfSight {
+            transparent: LifeCell::Dead,
+        };
+        // Sees through the dead cells to the live one in the middle, and past the edges otherwise
+        assert_eq!(vec![(1, 1)], g.neighbours(0, 0, &line_of_sight));
+        assert_eq!(vec![(1, 1)], g.neighbours(2, 2, &line_of_sight));
+        assert_eq!(
+            vec![(2, 2), (0, 0)],
+            g.neighbours(1, 1, &line_of_sight)
+                .into_iter()
+                .filter(|(i, j)| g.get(*i, *j) == Some(&LifeCell::Alive))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn adjacency_test() {
+        let g = life(
+            "#..
+.#.
+..#
+",
+        );
+        let line_of_sight = Neighbourhood::LineOfSight {
+            transparent: LifeCell::Dead,
+        };
+        let adjacency = Adjacency::new(&g, &line_of_sight);
+        assert_eq!(9, adjacency.len());
+        for cell in 0..adjacency.len() {
+            let (i, j) = adjacency.position(cell);
+            let expected: Vec<_> = g
+                .neighbours(i, j, &line_of_sight)
+                .into_iter()
+                .map(|(i, j)| i * 3 + j)
+                .collect();
+            assert_eq!(expected, adjacency.neighbours(cell));
+        }
+
+        // Only looks right, so the cell to the left watches
+        let adjacency = Adjacency::new(&g, &Neighbourhood::Offsets(vec![(0, 1)]));
+        assert_eq!(&[5], adjacency.neighbours(4));
+        assert_eq!(&[3], adjacency.watchers(4));
+        assert!(adjacency.watchers(3).is_empty());
+    }
+
+    #[test]
+    fn only_changed_cells_are_revisited_test() {
+        let mut simulation = Simulation::new(
+            life(
+                "......
+......
+..#...
+..#...
+..#...
+......
+",
+            ),
+            RuleSet::life(),
+        );
+        assert_eq!(36, simulation.pending());
+        simulation.step();
+        // The four cells that changed and everything around them
+        assert_eq!(21, simulation.pending());
+
+        let mut simulation = Simulation::new(
+            life(
+                "......
+......
+..##..
+..##..
+......
+......
+",
+            ),
+            RuleSet::life(),
+        );
+        assert_eq!(Outcome::Stabilised { generation: 0 }, simulation.run());
+        assert_eq!(0, simulation.pending());
+        assert_eq!(4, simulation.count(LifeCell::Alive));
+    }
+
+    #[test]
+    fn life_blinker_test() {
+        let mut simulation = Simulation::new(
+            life(
+                ".....
+..#..
+..#..
+..#..
+.....
+",
+            ),
+            RuleSet::life(),
+        );
+        simulation.step();
+        assert_eq!(
+            "\
+.....
+.....
+.###.
+.....
+.....
+",
+            simulation.grid().to_string()
+        );
+        simulation.step();
+        assert_eq!(
+            "\
+.....
+..#..
+..#..
+..#..
+.....
+",
+            simulation.grid().to_string()
+        );
+    }
+
+    #[test]
+    fn life_block_is_stable_test() {
+        let mut simulation = Simulation::new(
+            life(
+                "....
+.##.
+.##.
+....
+",
+            ),
+            RuleSet::life(),
+        );
+        assert_eq!(Outcome::Stabilised { generation: 0 }, simulation.run());
+        assert_eq!(4, simulation.count(LifeCell::Alive));
+    }
+
+    #[test]
+    fn blinker_cycles_test() {
+        let blinker = ".....
+..#..
+..#..
+..#..
+.....
+";
+        let mut simulation = Simulation::new(life(blinker), RuleSet::life());
+        let mut generations = Vec::new();
+        let outcome = simulation.run_with(|generation, grid| {
+            generations.push((generation, grid.count(LifeCell::Alive)))
+        });
+        assert_eq!(
+            Outcome::Cycled {
+                start: 0,
+                period: 2
+            },
+            outcome
+        );
+        assert_eq!(vec![(0, 3), (1, 3)], generations);
+
+        let mut simulation = Simulation::new(life(blinker), RuleSet::life()).with_max_steps(1);
+        assert_eq!(Outcome::StepLimit { steps: 1 }, simulation.run());
+        assert_eq!(1, simulation.generation());
+    }
+
+    #[test]
+    fn cycle_after_stepping_test() {
+        // The earlier grid gets replayed from where the run started, not from generation 0
+        let blinker = ".....
+..#..
+..#..
+..#..
+.....
+";
+        let mut simulation = Simulation::new(life(blinker), RuleSet::life());
+        simulation.step();
+        simulation.step();
+        simulation.step();
+        assert_eq!(
+            Outcome::Cycled {
+                start: 3,
+                period: 2
+            },
+            simulation.run()
+        );
+        assert_eq!(5, simulation.generation());
+    }
+
+    #[test]
+    fn brians_brain_burns_out_test() {
+        let mut simulation = Simulation::new(
+            brain(
+                "....
+.##.
+....
+",
+            ),
+            RuleSet::brians_brain(),
+        )
+        .with_max_steps(100);
+        assert_eq!(Outcome::Stabilised { generation: 4 }, simulation.run());
+        assert_eq!(12, simulation.count(BrainCell::Off));
+    }
+
+    #[test]
+    fn changes_test() {
+        let mut simulation = Simulation::new(
+            life(
+                ".....
+..#..
+..#..
+..#..
+.....
+",
+            ),
+            RuleSet::life(),
+        );
+        simulation.step();
+        assert_eq!(4, simulation.changes());
+        simulation.step();
+        assert_eq!(4, simulation.changes());
+    }
+
+    // Steps every cell from scratch, to check the worklist and buffer swapping against
+    fn naive_step<S: Copy + Eq>(grid: &Grid<S>, rules: &RuleSet<S>) -> Grid<S> {
+        Grid(
+            grid.0
+                .iter()
+                .enumerate()
+                .map(|(i, row)| {
+                    row.iter()
+                        .enumerate()
+                        .map(|(j, cell)| {
+                            let neighbours = grid.neighbour_states(i, j, &rules.neighbourhood);
+                            rules.next_state(*cell, neighbours.iter().copied())
+                        })
+                        .collect()
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn matches_naive_stepping_test() {
+        // A big enough soup that the threads actually get used
+        let mut seed: u64 = 42;
+        let soup = Grid(
+            (0..80)
+                .map(|_| {
+                    (0..80)
+                        .map(|_| {
+                            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+                            if seed >> 62 == 0 {
+                                BrainCell::On
+                            } else {
+                                BrainCell::Off
+                            }
+                        })
+                        .collect()
+                })
+                .collect(),
+        );
+        let rules = RuleSet::brians_brain();
+        let mut single = Simulation::new(soup.clone(), rules.clone());
+        let mut threaded = Simulation::new(soup.clone(), rules.clone()).with_threads(4);
+        let mut expected = soup;
+        for _ in 0..30 {
+            expected = naive_step(&expected, &rules);
+            single.step();
+            threaded.step();
+            assert_eq!(&expected, single.grid());
+            assert_eq!(&expected, threaded.grid());
+        }
+    }
+
+    #[test]
+    fn brians_brain_test() {
+        let mut simulation = Simulation::new(
+            brain(
+                "....
+.##.
+....
+",
+            ),
+            RuleSet::brians_brain(),
+        );
+        simulation.step();
+        assert_eq!(
+            "\
+.##.
+.oo.
+.##.
+",
+            simulation.grid().to_string()
+        );
+        simulation.step();
+        assert_eq!(
+            "\
+.oo.
+#..#
+.oo.
+",
+            simulation.grid().to_string()
+        );
+    }
+}
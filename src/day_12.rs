@@ -2,39 +2,121 @@ use anyhow::Result;
 use combine::easy;
 use combine::parser::char::*;
 use combine::*;
+use std::fmt;
+use std::fs;
 use std::num::ParseIntError;
+use std::path::Path;
 use std::result::Result as StdResult;
 
+use route::Route;
 use Action::*;
-use Degrees::*;
+
+mod planner;
+mod route;
 
 const INPUT: &str = include_str!("../data/day_12_input");
 
-pub fn run() -> Result<()> {
+// If `svg_dir` is given, the routes are also saved there as day_12_ship.svg and
+// day_12_waypoint.svg
+pub fn run(svg_dir: Option<&Path>) -> Result<()> {
     println!("*** Day 12: Rain Risk ***");
     println!("Input: {}", INPUT);
     let actions = parse(INPUT)?;
 
-    let mut simulation = Interpreter::new(actions.clone());
-    simulation.run();
-    let solution_1 = simulation.current_coords.manhattan_distance();
+    let mut simulation = Navigator::new(Semantics::ShipRelative);
+    simulation.run(&actions);
+    describe_route(&simulation.route(), svg_dir, "day_12_ship.svg")?;
+    let solution_1 = simulation.position().manhattan_distance();
     println!("Solution 1: {:?}", solution_1);
 
-    let mut simulation_2 = WaypointInterpreter::new(actions);
-    simulation_2.run();
-    let solution_2 = simulation_2.current_coords.manhattan_distance();
+    let mut simulation_2 = Navigator::new(Semantics::Waypoint);
+    simulation_2.run(&actions);
+    describe_route(&simulation_2.route(), svg_dir, "day_12_waypoint.svg")?;
+    let solution_2 = simulation_2.position().manhattan_distance();
     println!("Solution 2: {:?}", solution_2);
 
+    for (semantics, target) in [
+        (Semantics::ShipRelative, simulation.position()),
+        (Semantics::Waypoint, simulation_2.position()),
+    ]
+    .iter()
+    {
+        let plan = planner::plan(*semantics, *target);
+        println!(
+            "Shortest {:?} route to ({}, {}): {}",
+            semantics,
+            target.x,
+            target.y,
+            plan.iter()
+                .map(|a| a.to_string())
+                .collect::<Vec<_>>()
+                .join(" ")
+        );
+    }
+
+    Ok(())
+}
+
+fn describe_route(route: &Route, svg_dir: Option<&Path>, svg_name: &str) -> Result<()> {
+    if let Some(bounds) = route.bounding_box() {
+        println!(
+            "Route spans ({}, {}) to ({}, {}), {} x {}",
+            bounds.min.x,
+            bounds.min.y,
+            bounds.max.x,
+            bounds.max.y,
+            bounds.width(),
+            bounds.height()
+        );
+    }
+    println!("Distance travelled: {:.1}", route.distance_travelled());
+    print!("{}", route.to_ascii(72, 24));
+    if let Some(dir) = svg_dir {
+        let path = dir.join(svg_name);
+        fs::write(&path, route.to_svg())?;
+        println!("Wrote {}", path.display());
+    }
     Ok(())
 }
 
+// A rotation that's been checked to be a whole number of quarter turns
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
-enum Degrees {
-    _90,
-    _180,
-    _270,
+struct Degrees(usize);
+
+impl Degrees {
+    fn new(degrees: usize) -> StdResult<Degrees, InvalidRotation> {
+        if degrees % 90 == 0 {
+            Ok(Degrees(degrees))
+        } else {
+            Err(InvalidRotation(degrees))
+        }
+    }
+
+    // How many quarter turns clockwise this works out to, from 0 to 3
+    fn clockwise_quarter_turns(&self, to: &RotateTo) -> usize {
+        let turns = self.0 / 90 % 4;
+        match to {
+            RotateTo::Right => turns,
+            RotateTo::Left => (4 - turns) % 4,
+        }
+    }
 }
 
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+struct InvalidRotation(usize);
+
+impl fmt::Display for InvalidRotation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "can only turn by multiples of 90 degrees, not {} degrees",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for InvalidRotation {}
+
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 enum RotateTo {
     Left,
@@ -49,41 +131,14 @@ enum Direction {
     West,
 }
 
-static DIRECTIONS: [Direction; 4] = [
-    Direction::North,
-    Direction::East,
-    Direction::South,
-    Direction::West,
-];
-
 impl Direction {
-    fn rotate(&self, to: &RotateTo, rotate: &Degrees) -> Direction {
-        let diff = match rotate {
-            Degrees::_90 => 1,
-            Degrees::_180 => 2,
-            Degrees::_270 => 3,
-        };
-        let current_idx = match self {
-            Direction::North => 0,
-            Direction::East => 1,
-            Direction::South => 2,
-            Direction::West => 3,
-        };
-
-        let rotated_idx = match to {
-            RotateTo::Left => -diff,
-            RotateTo::Right => diff,
-        };
-
-        let new_idx: isize = current_idx + rotated_idx;
-        let usize_idx = if new_idx < 0 {
-            DIRECTIONS.len() - (new_idx.abs() as usize % DIRECTIONS.len())
-        } else if new_idx >= DIRECTIONS.len() as isize {
-            (new_idx % DIRECTIONS.len() as isize) as usize
-        } else {
-            new_idx as usize
-        };
-        DIRECTIONS[usize_idx]
+    fn unit(&self) -> Coords {
+        match self {
+            Direction::North => Coords { x: 0, y: 1 },
+            Direction::East => Coords { x: 1, y: 0 },
+            Direction::South => Coords { x: 0, y: -1 },
+            Direction::West => Coords { x: -1, y: 0 },
+        }
     }
 }
 
@@ -97,6 +152,21 @@ enum Action {
     Forward(usize),
 }
 
+// In the same format the actions are parsed from
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            North(by) => write!(f, "N{}", by),
+            South(by) => write!(f, "S{}", by),
+            East(by) => write!(f, "E{}", by),
+            West(by) => write!(f, "W{}", by),
+            Rotate(RotateTo::Left, Degrees(by)) => write!(f, "L{}", by),
+            Rotate(RotateTo::Right, Degrees(by)) => write!(f, "R{}", by),
+            Forward(by) => write!(f, "F{}", by),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 struct Coords {
     x: isize,
@@ -107,143 +177,99 @@ impl Coords {
     fn manhattan_distance(&self) -> usize {
         (self.x.abs() + self.y.abs()) as usize
     }
-}
-
-struct Interpreter {
-    current_direction: Direction,
-    current_coords: Coords,
-    instructions: Vec<Action>,
-}
 
-impl Interpreter {
-    fn new(instructions: Vec<Action>) -> Interpreter {
-        Interpreter {
-            current_coords: Coords { x: 0, y: 0 },
-            current_direction: Direction::East,
-            instructions,
+    fn add(&self, other: Coords) -> Coords {
+        Coords {
+            x: self.x + other.x,
+            y: self.y + other.y,
         }
     }
 
-    fn run(&mut self) {
-        for action in self.instructions.iter() {
-            match action {
-                North(by) => {
-                    // self.current_direction = Direction::North;
-                    self.current_coords.y += *by as isize;
-                }
-                East(by) => {
-                    // self.current_direction = Direction::East;
-                    self.current_coords.x += *by as isize;
-                }
-                South(by) => {
-                    // self.current_direction = Direction::South;
-                    self.current_coords.y -= *by as isize;
-                }
-                West(by) => {
-                    // self.current_direction = Direction::West;
-                    self.current_coords.x -= *by as isize;
-                }
-                Forward(by) => match self.current_direction {
-                    Direction::North => self.current_coords.y += *by as isize,
-                    Direction::East => self.current_coords.x += *by as isize,
-                    Direction::South => self.current_coords.y -= *by as isize,
-                    Direction::West => self.current_coords.x -= *by as isize,
-                },
-                Rotate(to, by) => self.current_direction = self.current_direction.rotate(to, by),
-            }
+    fn scale(&self, by: usize) -> Coords {
+        Coords {
+            x: self.x * by as isize,
+            y: self.y * by as isize,
         }
     }
-}
-
-struct WaypointInterpreter {
-    waypoint: WayPoint,
-    current_coords: Coords,
-    instructions: Vec<Action>,
-}
 
-impl WaypointInterpreter {
-    fn new(instructions: Vec<Action>) -> WaypointInterpreter {
-        WaypointInterpreter {
-            waypoint: WayPoint { x: 10, y: 1 },
-            current_coords: Coords { x: 0, y: 0 },
-            instructions,
-        }
+    // Rotates around the origin
+    fn rotate(&self, to: &RotateTo, by: &Degrees) -> Coords {
+        (0..by.clockwise_quarter_turns(to)).fold(*self, |c, _| Coords { x: c.y, y: -c.x })
     }
+}
 
-    fn run(&mut self) {
-        for action in self.instructions.iter() {
-            match action {
-                North(by) => {
-                    // self.current_direction = Direction::North;
-                    self.waypoint.move_north(*by);
-                }
-                East(by) => {
-                    // self.current_direction = Direction::East;
-                    self.waypoint.move_east(*by);
-                }
-                South(by) => {
-                    // self.current_direction = Direction::South;
-                    self.waypoint.move_south(*by);
-                }
-                West(by) => {
-                    // self.current_direction = Direction::West;
-                    self.waypoint.move_west(*by);
-                }
-                Forward(by) => {
-                    let x_diff = *by as isize * self.waypoint.x;
-                    let y_diff = *by as isize * self.waypoint.y;
-                    self.current_coords.x += x_diff;
-                    self.current_coords.y += y_diff;
-                }
-                Rotate(to, by) => self.waypoint.rotate(to, by),
-            }
-        }
-    }
+// What N, S, E and W move: the ship itself, or the waypoint it's steering towards
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Semantics {
+    ShipRelative,
+    Waypoint,
 }
 
+// The ship always moves forward along `heading`, which is a unit vector for ship relative
+// navigation, or the waypoint (relative to the ship) otherwise. Rotating turns the heading in both
+// cases.
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
-struct WayPoint {
-    // units relative to the ship
-    x: isize,
-    y: isize,
+struct Ship {
+    position: Coords,
+    heading: Coords,
 }
 
-impl WayPoint {
-    fn move_north(&mut self, amt: usize) {
-        self.y += amt as isize;
-    }
+struct Navigator {
+    semantics: Semantics,
+    ship: Ship,
+    // The ship as it was before the first action, and after each one
+    path: Vec<Ship>,
+}
 
-    fn move_east(&mut self, amt: usize) {
-        self.x += amt as isize;
+impl Navigator {
+    fn new(semantics: Semantics) -> Navigator {
+        let heading = match semantics {
+            Semantics::ShipRelative => Direction::East.unit(),
+            Semantics::Waypoint => Coords { x: 10, y: 1 },
+        };
+        let ship = Ship {
+            position: Coords { x: 0, y: 0 },
+            heading,
+        };
+        Navigator {
+            semantics,
+            ship,
+            path: vec![ship],
+        }
     }
 
-    fn move_south(&mut self, amt: usize) {
-        self.y -= amt as isize;
+    fn position(&self) -> Coords {
+        self.ship.position
     }
 
-    fn move_west(&mut self, amt: usize) {
-        self.x -= amt as isize;
+    fn route(&self) -> Route<'_> {
+        Route::new(self.semantics, &self.path)
     }
 
-    fn rotate(&mut self, to: &RotateTo, by: &Degrees) {
-        // right-biased rotation
-        let rotate_multiplier = if to == &RotateTo::Right { -1 } else { 1 };
-        match *by {
-            Degrees::_90 => {
-                //
-                let temp_y = self.y;
-                self.y = rotate_multiplier * self.x;
-                self.x = -rotate_multiplier * temp_y;
-            }
-            Degrees::_180 => {
-                self.x = -self.x;
-                self.y = -self.y;
-            }
-            Degrees::_270 => {
-                let temp_y = self.y;
-                self.y = -rotate_multiplier * self.x;
-                self.x = rotate_multiplier * temp_y
+    fn apply(&mut self, action: &Action) {
+        match action {
+            North(by) => self.shift(Direction::North.unit().scale(*by)),
+            East(by) => self.shift(Direction::East.unit().scale(*by)),
+            South(by) => self.shift(Direction::South.unit().scale(*by)),
+            West(by) => self.shift(Direction::West.unit().scale(*by)),
+            Forward(by) => {
+                self.ship.position = self.ship.position.add(self.ship.heading.scale(*by));
             }
+            Rotate(to, by) => self.ship.heading = self.ship.heading.rotate(to, by),
+        }
+        self.path.push(self.ship);
+    }
+
+    fn run<'a>(&mut self, actions: impl IntoIterator<Item = &'a Action>) {
+        for action in actions {
+            self.apply(action);
+        }
+    }
+
+    fn shift(&mut self, by: Coords) {
+        match self.semantics {
+            Semantics::ShipRelative => self.ship.position = self.ship.position.add(by),
+            Semantics::Waypoint => self.ship.heading = self.ship.heading.add(by),
         }
     }
 }
@@ -303,12 +329,13 @@ fn degrees_parser<Input>() -> impl Parser<Input, Output = Degrees>
 where
     Input: Stream<Token = char>,
     Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+    <<Input as StreamOnce>::Error as combine::ParseError<
+        char,
+        <Input as StreamOnce>::Range,
+        <Input as StreamOnce>::Position,
+    >>::StreamError: From<ParseIntError> + From<InvalidRotation>,
 {
-    choice!(
-        string("90").map(|_| _90),
-        string("180").map(|_| _180),
-        string("270").map(|_| _270)
-    )
+    number_parser().and_then(Degrees::new)
 }
 
 #[cfg(test)]
@@ -316,6 +343,10 @@ mod tests {
     use super::*;
     use RotateTo::*;
 
+    fn degrees(degrees: usize) -> Degrees {
+        Degrees::new(degrees).unwrap()
+    }
+
     #[test]
     fn parse_simple_test() {
         let input = "F10
@@ -329,7 +360,7 @@ F11
             Forward(10),
             North(3),
             Forward(7),
-            Rotate(Right, _90),
+            Rotate(Right, degrees(90)),
             Forward(11),
         ];
         assert_eq!(expected, r);
@@ -338,12 +369,12 @@ F11
     #[test]
     fn direction_rotate_test() {
         use Direction::*;
-        assert_eq!(East, North.rotate(&Right, &_90));
-        assert_eq!(South, North.rotate(&Right, &_180));
-        assert_eq!(West, North.rotate(&Right, &_270));
-        assert_eq!(West, North.rotate(&Left, &_90));
-        assert_eq!(South, North.rotate(&Left, &_180));
-        assert_eq!(East, North.rotate(&Left, &_270));
+        assert_eq!(East.unit(), North.unit().rotate(&Right, &degrees(90)));
+        assert_eq!(South.unit(), North.unit().rotate(&Right, &degrees(180)));
+        assert_eq!(West.unit(), North.unit().rotate(&Right, &degrees(270)));
+        assert_eq!(West.unit(), North.unit().rotate(&Left, &degrees(90)));
+        assert_eq!(South.unit(), North.unit().rotate(&Left, &degrees(180)));
+        assert_eq!(East.unit(), North.unit().rotate(&Left, &degrees(270)));
     }
 
     #[test]
@@ -352,12 +383,12 @@ F11
             Forward(10),
             North(3),
             Forward(7),
-            Rotate(Right, _90),
+            Rotate(Right, degrees(90)),
             Forward(11),
         ];
-        let mut simulation = Interpreter::new(instructions);
-        simulation.run();
-        assert_eq!(Coords { x: 17, y: -8 }, simulation.current_coords)
+        let mut simulation = Navigator::new(Semantics::ShipRelative);
+        simulation.run(&instructions);
+        assert_eq!(Coords { x: 17, y: -8 }, simulation.position())
     }
 
     #[test]
@@ -367,21 +398,21 @@ F11
 
     #[test]
     fn way_point_rotate_test() {
-        let mut way_point = WayPoint { x: 2, y: 1 };
-        way_point.rotate(&Right, &_90);
-        assert_eq!(WayPoint { x: 1, y: -2 }, way_point);
-        way_point.rotate(&Right, &_90);
-        assert_eq!(WayPoint { x: -2, y: -1 }, way_point);
-        way_point.rotate(&Right, &_90);
-        assert_eq!(WayPoint { x: -1, y: 2 }, way_point);
-        way_point.rotate(&Right, &_180);
-        assert_eq!(WayPoint { x: 1, y: -2 }, way_point);
-        way_point.rotate(&Left, &_180);
-        assert_eq!(WayPoint { x: -1, y: 2 }, way_point);
-        way_point.rotate(&Left, &_270);
-        assert_eq!(WayPoint { x: 2, y: 1 }, way_point);
-        way_point.rotate(&Left, &_90);
-        assert_eq!(WayPoint { x: -1, y: 2 }, way_point);
+        let mut way_point = Coords { x: 2, y: 1 };
+        way_point = way_point.rotate(&Right, &degrees(90));
+        assert_eq!(Coords { x: 1, y: -2 }, way_point);
+        way_point = way_point.rotate(&Right, &degrees(90));
+        assert_eq!(Coords { x: -2, y: -1 }, way_point);
+        way_point = way_point.rotate(&Right, &degrees(90));
+        assert_eq!(Coords { x: -1, y: 2 }, way_point);
+        way_point = way_point.rotate(&Right, &degrees(180));
+        assert_eq!(Coords { x: 1, y: -2 }, way_point);
+        way_point = way_point.rotate(&Left, &degrees(180));
+        assert_eq!(Coords { x: -1, y: 2 }, way_point);
+        way_point = way_point.rotate(&Left, &degrees(270));
+        assert_eq!(Coords { x: 2, y: 1 }, way_point);
+        way_point = way_point.rotate(&Left, &degrees(90));
+        assert_eq!(Coords { x: -1, y: 2 }, way_point);
     }
 
     #[test]
@@ -390,11 +421,36 @@ F11
             Forward(10),
             North(3),
             Forward(7),
-            Rotate(Right, _90),
+            Rotate(Right, degrees(90)),
             Forward(11),
         ];
-        let mut simulation = WaypointInterpreter::new(instructions);
-        simulation.run();
-        assert_eq!(Coords { x: 214, y: -72 }, simulation.current_coords)
+        let mut simulation = Navigator::new(Semantics::Waypoint);
+        simulation.run(&instructions);
+        assert_eq!(Coords { x: 214, y: -72 }, simulation.position())
+    }
+
+    #[test]
+    fn any_multiple_of_90_test() {
+        use Direction::*;
+        assert_eq!(North.unit(), North.unit().rotate(&Right, &degrees(0)));
+        assert_eq!(North.unit(), North.unit().rotate(&Left, &degrees(360)));
+        assert_eq!(East.unit(), North.unit().rotate(&Right, &degrees(450)));
+        assert_eq!(South.unit(), North.unit().rotate(&Left, &degrees(900)));
+        assert_eq!(
+            Coords { x: -1, y: 2 },
+            Coords { x: 2, y: 1 }.rotate(&Left, &degrees(810))
+        );
+    }
+
+    #[test]
+    fn invalid_rotation_test() {
+        assert_eq!(Err(InvalidRotation(45)), Degrees::new(45));
+        assert_eq!(vec![Rotate(Left, degrees(720))], parse("L720\n").unwrap());
+        let error = parse("F10\nR45\n").unwrap_err().to_string();
+        assert!(
+            error.contains("can only turn by multiples of 90 degrees, not 45 degrees"),
+            "{}",
+            error
+        );
     }
 }
@@ -0,0 +1,163 @@
+use super::{Action, Action::*, Coords, Degrees, Navigator, RotateTo, Semantics};
+
+// A shortest (fewest actions) program that sails from the origin to `target`.
+//
+// Ship relative, N/S/E/W move the ship directly, so it's at most one move along each axis.
+//
+// With a waypoint, only F moves the ship, by a whole multiple of the waypoint. Any target can be
+// reached in three actions (move the waypoint onto it along both axes, then F1), so we only need
+// to look for something shorter: a single F, or one change to the waypoint followed by an F.
+pub fn plan(semantics: Semantics, target: Coords) -> Vec<Action> {
+    if target == (Coords { x: 0, y: 0 }) {
+        return vec![];
+    }
+    match semantics {
+        Semantics::ShipRelative => shift(target).iter().flatten().copied().collect(),
+        Semantics::Waypoint => {
+            let waypoint = Navigator::new(semantics).ship.heading;
+            one_forward(waypoint, target)
+                .or_else(|| waypoint_change_then_forward(waypoint, target))
+                .unwrap_or_else(|| {
+                    let [x, y] = shift(Coords {
+                        x: target.x - waypoint.x,
+                        y: target.y - waypoint.y,
+                    });
+                    x.into_iter().chain(y).chain(Some(Forward(1))).collect()
+                })
+        }
+    }
+}
+
+// The moves that shift something by `by`, one per axis if it needs moving along that axis
+fn shift(by: Coords) -> [Option<Action>; 2] {
+    let x = match by.x {
+        0 => None,
+        x if x > 0 => Some(East(x as usize)),
+        x => Some(West(x.unsigned_abs())),
+    };
+    let y = match by.y {
+        0 => None,
+        y if y > 0 => Some(North(y as usize)),
+        y => Some(South(y.unsigned_abs())),
+    };
+    [x, y]
+}
+
+// How many times `step` fits into `target`, if it does exactly and a positive number of times
+fn multiple(step: Coords, target: Coords) -> Option<usize> {
+    let times = if step.x != 0 {
+        target.x / step.x
+    } else if step.y != 0 {
+        target.y / step.y
+    } else {
+        return None;
+    };
+    if times > 0 && step.scale(times as usize) == target {
+        Some(times as usize)
+    } else {
+        None
+    }
+}
+
+fn one_forward(waypoint: Coords, target: Coords) -> Option<Vec<Action>> {
+    multiple(waypoint, target).map(|times| vec![Forward(times)])
+}
+
+fn waypoint_change_then_forward(waypoint: Coords, target: Coords) -> Option<Vec<Action>> {
+    let turns = [
+        (RotateTo::Right, 90),
+        (RotateTo::Right, 180),
+        (RotateTo::Left, 90),
+    ];
+    let rotated = turns.iter().map(|(to, by)| {
+        let degrees = Degrees(*by);
+        (Rotate(*to, degrees), waypoint.rotate(to, &degrees))
+    });
+
+    // Keep one coordinate of the waypoint and pick the other so the target is a multiple of it:
+    // the kept coordinate fixes how many times we have to go forward
+    let along_y = Some(waypoint.x)
+        .filter(|x| *x != 0 && target.x % x == 0 && target.x / x > 0)
+        .map(|x| target.x / x)
+        .filter(|times| target.y % times == 0)
+        .map(|times| Coords {
+            x: 0,
+            y: target.y / times - waypoint.y,
+        });
+    let along_x = Some(waypoint.y)
+        .filter(|y| *y != 0 && target.y % y == 0 && target.y / y > 0)
+        .map(|y| target.y / y)
+        .filter(|times| target.x % times == 0)
+        .map(|times| Coords {
+            x: target.x / times - waypoint.x,
+            y: 0,
+        });
+    let shifted = along_x.into_iter().chain(along_y).filter_map(|by| {
+        let [x, y] = shift(by);
+        x.or(y).map(|action| (action, waypoint.add(by)))
+    });
+
+    rotated.chain(shifted).find_map(|(change, waypoint)| {
+        multiple(waypoint, target).map(|times| vec![change, Forward(times)])
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::parse;
+    use super::*;
+
+    fn replay(semantics: Semantics, actions: &[Action]) -> Coords {
+        let mut navigator = Navigator::new(semantics);
+        navigator.run(actions);
+        navigator.position()
+    }
+
+    #[test]
+    fn ship_relative_test() {
+        let plan_to = |x, y| plan(Semantics::ShipRelative, Coords { x, y });
+        assert_eq!(Vec::<Action>::new(), plan_to(0, 0));
+        assert_eq!(vec![West(4)], plan_to(-4, 0));
+        assert_eq!(vec![East(17), South(8)], plan_to(17, -8));
+    }
+
+    #[test]
+    fn waypoint_test() {
+        let plan_to = |x, y| plan(Semantics::Waypoint, Coords { x, y });
+        assert_eq!(vec![Forward(7)], plan_to(70, 7));
+        assert_eq!(
+            vec![Rotate(RotateTo::Right, Degrees(90)), Forward(3)],
+            plan_to(3, -30)
+        );
+        assert_eq!(vec![East(2), Forward(5)], plan_to(60, 5));
+        assert_eq!(vec![North(3), Forward(2)], plan_to(20, 8));
+        assert_eq!(vec![West(27), South(9), Forward(1)], plan_to(-17, -8));
+        assert_eq!(vec![West(10), Forward(5)], plan_to(0, 5));
+    }
+
+    #[test]
+    fn plans_replay_to_their_targets_test() {
+        for semantics in [Semantics::ShipRelative, Semantics::Waypoint].iter() {
+            for x in -60..=60 {
+                for y in -30..=30 {
+                    let target = Coords { x, y };
+                    let plan = plan(*semantics, target);
+                    assert_eq!(target, replay(*semantics, &plan), "{:?}", plan);
+                    assert!(plan.len() <= 3);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn plans_are_valid_programs_test() {
+        let target = Coords { x: 214, y: -72 };
+        let plan = plan(Semantics::Waypoint, target);
+        let program: String = plan.iter().map(|a| format!("{}\n", a)).collect();
+        assert_eq!(plan, parse(&program).unwrap());
+        assert_eq!(
+            target,
+            replay(Semantics::Waypoint, &parse(&program).unwrap())
+        );
+    }
+}
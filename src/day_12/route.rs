@@ -0,0 +1,273 @@
+use std::fmt::Write;
+
+use super::{Coords, Semantics, Ship};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct BoundingBox {
+    pub min: Coords,
+    pub max: Coords,
+}
+
+impl BoundingBox {
+    fn around(points: impl IntoIterator<Item = Coords>) -> Option<BoundingBox> {
+        points.into_iter().fold(None, |bounds, c| {
+            Some(match bounds {
+                None => BoundingBox { min: c, max: c },
+                Some(BoundingBox { min, max }) => BoundingBox {
+                    min: Coords {
+                        x: min.x.min(c.x),
+                        y: min.y.min(c.y),
+                    },
+                    max: Coords {
+                        x: max.x.max(c.x),
+                        y: max.y.max(c.y),
+                    },
+                },
+            })
+        })
+    }
+
+    pub fn width(&self) -> usize {
+        (self.max.x - self.min.x) as usize
+    }
+
+    pub fn height(&self) -> usize {
+        (self.max.y - self.min.y) as usize
+    }
+}
+
+// Everywhere a navigator has been, one ship per action (plus where it started)
+pub struct Route<'a> {
+    semantics: Semantics,
+    ships: &'a [Ship],
+}
+
+impl<'a> Route<'a> {
+    pub fn new(semantics: Semantics, ships: &'a [Ship]) -> Route<'a> {
+        Route { semantics, ships }
+    }
+
+    pub fn positions(&self) -> impl Iterator<Item = Coords> + '_ {
+        self.ships.iter().map(|ship| ship.position)
+    }
+
+    // Where the waypoint was on the map (rather than relative to the ship), if we were using one
+    pub fn waypoints(&self) -> Option<Vec<Coords>> {
+        match self.semantics {
+            Semantics::ShipRelative => None,
+            Semantics::Waypoint => Some(
+                self.ships
+                    .iter()
+                    .map(|ship| ship.position.add(ship.heading))
+                    .collect(),
+            ),
+        }
+    }
+
+    // Around the ship's positions only; the waypoint can wander well off the route
+    pub fn bounding_box(&self) -> Option<BoundingBox> {
+        BoundingBox::around(self.positions())
+    }
+
+    // Ships sail in straight lines, so this is the length of the route as the crow flies between
+    // positions, not the Manhattan distance
+    pub fn distance_travelled(&self) -> f64 {
+        self.ships
+            .windows(2)
+            .map(|pair| {
+                let dx = (pair[1].position.x - pair[0].position.x) as f64;
+                let dy = (pair[1].position.y - pair[0].position.y) as f64;
+                dx.hypot(dy)
+            })
+            .sum()
+    }
+
+    // North is up, so y is flipped to fit SVG's downward y axis
+    pub fn to_svg(&self) -> String {
+        let bounds = match self.bounding_box() {
+            Some(bounds) => bounds,
+            None => return String::new(),
+        };
+        let margin = ((bounds.width().max(bounds.height()) / 20).max(1)) as isize;
+        let points = |coords: &mut dyn Iterator<Item = Coords>| {
+            coords
+                .map(|c| format!("{},{}", c.x, -c.y))
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+
+        let mut svg = String::new();
+        // Writing to a String can't fail
+        let _ = writeln!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{} {} {} {}">"#,
+            bounds.min.x - margin,
+            -bounds.max.y - margin,
+            bounds.width() as isize + 2 * margin,
+            bounds.height() as isize + 2 * margin
+        );
+        if let Some(waypoints) = self.waypoints() {
+            let _ = writeln!(
+                svg,
+                r#"  <polyline points="{}" fill="none" stroke="grey" stroke-dasharray="4" vector-effect="non-scaling-stroke"/>"#,
+                points(&mut waypoints.into_iter())
+            );
+        }
+        let _ = writeln!(
+            svg,
+            r#"  <polyline points="{}" fill="none" stroke="navy" vector-effect="non-scaling-stroke"/>"#,
+            points(&mut self.positions())
+        );
+        for (position, colour) in [
+            (self.positions().next(), "green"),
+            (self.positions().last(), "red"),
+        ]
+        .iter()
+        {
+            if let Some(c) = position {
+                let _ = writeln!(
+                    svg,
+                    r#"  <circle cx="{}" cy="{}" r="{}" fill="{}"/>"#,
+                    c.x, -c.y, margin, colour
+                );
+            }
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    // Squashes the route into a `columns` by `rows` box of characters: `S` is the start, `E` the
+    // end, and `*` everything in between
+    pub fn to_ascii(&self, columns: usize, rows: usize) -> String {
+        let bounds = match self.bounding_box() {
+            Some(bounds) if columns > 0 && rows > 0 => bounds,
+            _ => return String::new(),
+        };
+        let scale = |value: isize, min: isize, span: usize, cells: usize| -> isize {
+            if span == 0 {
+                0
+            } else {
+                ((value - min) as f64 / span as f64 * (cells - 1) as f64).round() as isize
+            }
+        };
+        let to_cell = |c: Coords| {
+            (
+                (rows - 1) as isize - scale(c.y, bounds.min.y, bounds.height(), rows),
+                scale(c.x, bounds.min.x, bounds.width(), columns),
+            )
+        };
+
+        let mut plot = vec![vec!['.'; columns]; rows];
+        let cells: Vec<_> = self.positions().map(to_cell).collect();
+        for pair in cells.windows(2) {
+            let ((r0, c0), (r1, c1)) = (pair[0], pair[1]);
+            let steps = (r1 - r0).abs().max((c1 - c0).abs()).max(1);
+            for step in 0..=steps {
+                let r = r0 + (r1 - r0) * step / steps;
+                let c = c0 + (c1 - c0) * step / steps;
+                plot[r as usize][c as usize] = '*';
+            }
+        }
+        if let (Some((r, c)), Some((end_r, end_c))) = (cells.first(), cells.last()) {
+            plot[*end_r as usize][*end_c as usize] = 'E';
+            plot[*r as usize][*c as usize] = 'S';
+        }
+
+        plot.into_iter()
+            .map(|row| {
+                let mut line: String = row.into_iter().collect();
+                line.push('\n');
+                line
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Action::*, Degrees, Navigator, RotateTo};
+    use super::*;
+
+    fn route_of(semantics: Semantics, actions: &[super::super::Action]) -> Navigator {
+        let mut navigator = Navigator::new(semantics);
+        navigator.run(actions);
+        navigator
+    }
+
+    #[test]
+    fn path_test() {
+        let navigator = route_of(Semantics::Waypoint, &[Forward(10), North(3), Forward(7)]);
+        let route = navigator.route();
+        assert_eq!(
+            vec![
+                Coords { x: 0, y: 0 },
+                Coords { x: 100, y: 10 },
+                Coords { x: 100, y: 10 },
+                Coords { x: 170, y: 38 },
+            ],
+            route.positions().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            Some(vec![
+                Coords { x: 10, y: 1 },
+                Coords { x: 110, y: 11 },
+                Coords { x: 110, y: 14 },
+                Coords { x: 180, y: 42 },
+            ]),
+            route.waypoints()
+        );
+
+        let navigator = route_of(Semantics::ShipRelative, &[Forward(10)]);
+        assert_eq!(None, navigator.route().waypoints());
+    }
+
+    #[test]
+    fn bounding_box_and_distance_test() {
+        let navigator = route_of(
+            Semantics::ShipRelative,
+            &[
+                Forward(3),
+                Rotate(RotateTo::Left, Degrees::new(90).unwrap()),
+                Forward(4),
+                South(10),
+                West(5),
+            ],
+        );
+        let route = navigator.route();
+        let bounds = route.bounding_box().unwrap();
+        assert_eq!(
+            BoundingBox {
+                min: Coords { x: -2, y: -6 },
+                max: Coords { x: 3, y: 4 },
+            },
+            bounds
+        );
+        assert_eq!((5, 10), (bounds.width(), bounds.height()));
+        assert!((route.distance_travelled() - 22.0).abs() < 1e-9);
+
+        // The diagonal of a 3-4-5 triangle
+        let navigator = route_of(Semantics::Waypoint, &[West(7), North(3), Forward(1)]);
+        assert!((navigator.route().distance_travelled() - 5.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn to_ascii_test() {
+        let navigator = route_of(Semantics::ShipRelative, &[Forward(2), North(1)]);
+        assert_eq!("..E\nS**\n", navigator.route().to_ascii(3, 2));
+
+        // Scaled down by half
+        let navigator = route_of(Semantics::ShipRelative, &[Forward(4), North(2), West(4)]);
+        assert_eq!("E**\n..*\nS**\n", navigator.route().to_ascii(3, 3));
+    }
+
+    #[test]
+    fn to_svg_test() {
+        let navigator = route_of(Semantics::Waypoint, &[Forward(2), North(1)]);
+        let svg = navigator.route().to_svg();
+        assert!(svg.starts_with(r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="-1 -3 22 4">"#));
+        assert!(svg.contains(r#"<polyline points="0,0 20,-2 20,-2" fill="none" stroke="navy""#));
+        assert!(svg.contains(r#"<polyline points="10,-1 30,-3 30,-4" fill="none" stroke="grey""#));
+        assert!(svg.contains(r#"<circle cx="20" cy="-2" r="1" fill="red"/>"#));
+        assert!(svg.ends_with("</svg>\n"));
+    }
+}
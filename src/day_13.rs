@@ -2,19 +2,39 @@ use anyhow::Result;
 use combine::easy;
 use combine::parser::char::*;
 use combine::*;
-use num::integer::lcm;
+use num::BigInt;
 use std::num::ParseIntError;
 use std::result::Result as StdResult;
 
+use constraints::{Constraint, ScheduleError};
+
+pub mod constraints;
+pub mod crt;
+
 const INPUT: &str = include_str!("../data/day_13_input");
 
-pub fn run() -> Result<()> {
+// `constraints` are extra constraints on top of the bus list, in the language parsed by
+// `constraints::parse_constraints`
+pub fn run(constraints: Option<&str>) -> Result<()> {
     println!("*** Day 13: Shuttle Search ***");
     println!("Input: {}", INPUT);
     let notes = parse(INPUT)?;
 
+    for bus in notes.timetable(notes.earliest_departure_minute, 3) {
+        let departures: Vec<_> = bus
+            .departures
+            .iter()
+            .map(|d| format!("{} (+{})", d.time, d.wait))
+            .collect();
+        println!("Bus {:>4}: {}", bus.id, departures.join(", "));
+    }
     println!("Solution 1: {:?}", notes.solution_1());
-    println!("Solution 2: {:?}", notes.solution_2());
+    println!("Solution 2: {}", notes.solution_2()?);
+
+    if let Some(constraints) = constraints {
+        let extra = constraints::parse_constraints(constraints)?;
+        println!("With constraints: {}", notes.earliest_timestamp(&extra)?);
+    }
 
     Ok(())
 }
@@ -25,86 +45,89 @@ struct Notes {
     buses: Vec<Bus>,
 }
 
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+struct Departure {
+    time: u128,
+    wait: u128,
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+struct BusTimetable {
+    id: u128,
+    departures: Vec<Departure>,
+}
+
+// Buses leave at every multiple of their id, so the first one at or after `after` is `after`
+// rounded up to the next multiple. Bus 0 would never go anywhere.
+fn next_departure(id: u128, after: u128) -> Option<Departure> {
+    let time = after.checked_next_multiple_of(id)?;
+    Some(Departure {
+        time,
+        wait: time - after,
+    })
+}
+
 impl Notes {
-    fn bus_departures(&self) -> impl Iterator<Item = (u128, Vec<&Bus>)> {
-        (0..).into_iter().filter_map(move |minute| {
-            let valid_buses: Vec<&Bus> = self
-                .buses
-                .iter()
-                .filter(|bus| {
-                    if let Bus::Id(id) = bus {
-                        minute % id == 0
-                    } else {
-                        false
-                    }
-                })
-                .collect();
-            if valid_buses.is_empty() {
-                None
-            } else {
-                Some((minute, valid_buses))
-            }
+    fn bus_ids(&self) -> impl Iterator<Item = u128> + '_ {
+        self.buses.iter().filter_map(|bus| match bus {
+            Bus::Id(id) => Some(*id),
+            Bus::NotInService => None,
         })
     }
 
+    // The first `count` departures of every bus at or after `after`
+    fn timetable(&self, after: u128, count: usize) -> Vec<BusTimetable> {
+        self.bus_ids()
+            .filter_map(|id| {
+                let first = next_departure(id, after)?;
+                let departures = (0..count as u128)
+                    .map(|n| Departure {
+                        time: first.time + n * id,
+                        wait: first.wait + n * id,
+                    })
+                    .collect();
+                Some(BusTimetable { id, departures })
+            })
+            .collect()
+    }
+
+    // If several buses leave at the same time, the one listed first wins
+    fn earliest_bus(&self) -> Option<(u128, Departure)> {
+        self.bus_ids()
+            .filter_map(|id| Some((id, next_departure(id, self.earliest_departure_minute)?)))
+            .min_by_key(|(_, departure)| departure.time)
+    }
+
     fn solution_1(&self) -> Option<u128> {
-        let (time, buses) = self
-            .bus_departures()
-            .find(|(time, _)| *time >= self.earliest_departure_minute)?;
-        let first_bus = buses.first()?;
-
-        if let Bus::Id(id) = first_bus {
-            Some(id * (time - self.earliest_departure_minute))
-        } else {
-            None
-        }
+        let (id, departure) = self.earliest_bus()?;
+        Some(id * departure.wait)
     }
 
-    fn solution_2(&self) -> Option<u128> {
-        let bus_ids_with_time_offsets: Vec<_> = self
-            .buses
+    // Bus `id` at index `idx` has to leave `idx` minutes after the first one
+    fn constraints(&self) -> Vec<Constraint> {
+        self.buses
             .iter()
             .enumerate()
-            .filter_map(|(idx, bus)| {
-                if let Bus::Id(id) = bus {
-                    Some((id, idx as u128))
-                } else {
-                    None
-                }
+            .filter_map(|(idx, bus)| match bus {
+                Bus::Id(id) => Some(Constraint::At {
+                    bus: *id,
+                    offset: idx as i128,
+                }),
+                Bus::NotInService => None,
             })
-            .collect();
+            .collect()
+    }
+
+    // The earliest t that meets the bus list's constraints along with `extra` ones. Bus ids
+    // don't have to be coprime, but then the offsets have to line up, or there's no such t at all.
+    fn earliest_timestamp(&self, extra: &[Constraint]) -> StdResult<BigInt, ScheduleError> {
+        let mut constraints = self.constraints();
+        constraints.extend_from_slice(extra);
+        constraints::solve(&constraints)
+    }
 
-        let (first_bus_id, _) = bus_ids_with_time_offsets.first()?;
-        let (bus_0_departure, _) = bus_ids_with_time_offsets.iter().skip(1).try_fold(
-            (0, **first_bus_id),
-            |(current_first_bus_departure, current_bus_group_period),
-             (next_bus_period, next_bus_offset)| {
-                // The next departure time for the first bus that matches the period and offset of the next bus
-                let next_bus_group_departure = (current_first_bus_departure..)
-                    .step_by(current_bus_group_period as usize)
-                    .find(|departure_time| {
-                        (*departure_time + next_bus_offset) % **next_bus_period == 0
-                    })?;
-
-                // The combined period for all the buses we've visited, including this new, "next" one.
-                // By incrementing with this "new" combined period, we ensure that the offsets
-                // of the previous visited buses are taken into account
-
-                // e.g. given (7, 13, x, x, 59)
-                // 1. Between 7 (offset 0) and 13 (offset 1), the first offset is 77, (77/7 = 1, 78/13 = 6)
-                //    New period is LCM of them, which is 91
-                // 2. Next, find one that works ith 59, offset 4:
-                //    [(77 + x *(91)) + 4]  % 59 = 0, where the first group is te next departure time,
-                //     just keep incrementing x
-                //    next departure time = 77 * 3 * 91  = 350
-                //    check: 350 / 7 = 50, 351/13 = 27, 354/59 = 6
-                let new_bus_group_period = lcm(current_bus_group_period, **next_bus_period);
-
-                Some((next_bus_group_departure, new_bus_group_period))
-            },
-        )?;
-
-        Some(bus_0_departure)
+    fn solution_2(&self) -> StdResult<BigInt, ScheduleError> {
+        self.earliest_timestamp(&[])
     }
 }
 
@@ -157,6 +180,7 @@ where
 
 #[cfg(test)]
 mod tests {
+    use super::crt::CrtError;
     use super::*;
 
     #[test]
@@ -214,7 +238,7 @@ mod tests {
                 Bus::Id(19),
             ],
         };
-        assert_eq!(Some(1068781), notes.solution_2())
+        assert_eq!(Ok(BigInt::from(1068781)), notes.solution_2())
     }
 
     #[test]
@@ -223,7 +247,7 @@ mod tests {
             earliest_departure_minute: 939,
             buses: vec![Bus::Id(17), Bus::NotInService, Bus::Id(13), Bus::Id(19)],
         };
-        assert_eq!(Some(3417), notes.solution_2())
+        assert_eq!(Ok(BigInt::from(3417)), notes.solution_2())
     }
 
     #[test]
@@ -232,7 +256,7 @@ mod tests {
             earliest_departure_minute: 939,
             buses: vec![Bus::Id(67), Bus::Id(7), Bus::Id(59), Bus::Id(61)],
         };
-        assert_eq!(Some(754018), notes.solution_2())
+        assert_eq!(Ok(BigInt::from(754018)), notes.solution_2())
     }
 
     #[test]
@@ -247,7 +271,7 @@ mod tests {
                 Bus::Id(61),
             ],
         };
-        assert_eq!(Some(779210), notes.solution_2())
+        assert_eq!(Ok(BigInt::from(779210)), notes.solution_2())
     }
 
     #[test]
@@ -262,7 +286,7 @@ mod tests {
                 Bus::Id(61),
             ],
         };
-        assert_eq!(Some(1261476), notes.solution_2())
+        assert_eq!(Ok(BigInt::from(1261476)), notes.solution_2())
     }
 
     #[test]
@@ -271,6 +295,121 @@ mod tests {
             earliest_departure_minute: 939,
             buses: vec![Bus::Id(1789), Bus::Id(37), Bus::Id(47), Bus::Id(1889)],
         };
-        assert_eq!(Some(1202161486), notes.solution_2())
+        assert_eq!(Ok(BigInt::from(1202161486)), notes.solution_2())
+    }
+
+    #[test]
+    fn solution_2_non_coprime_test() {
+        // 4 and 6 share a factor of 2: t ≡ 0 (mod 4) and t + 2 ≡ 0 (mod 6)
+        let notes = Notes {
+            earliest_departure_minute: 939,
+            buses: vec![Bus::Id(4), Bus::NotInService, Bus::Id(6)],
+        };
+        assert_eq!(Ok(BigInt::from(4)), notes.solution_2());
+
+        // t even and t + 1 even can't both happen
+        let notes = Notes {
+            earliest_departure_minute: 939,
+            buses: vec![Bus::Id(4), Bus::Id(6)],
+        };
+        assert!(matches!(
+            notes.solution_2(),
+            Err(ScheduleError::Unsolvable(CrtError::Inconsistent { .. }))
+        ));
+    }
+
+    #[test]
+    fn solution_2_bus_0_test() {
+        let notes = parse("939\n7,0,13\n").unwrap();
+        assert_eq!(
+            Err(ScheduleError::Unsolvable(CrtError::NonPositiveModulus(
+                BigInt::from(0)
+            ))),
+            notes.solution_2()
+        );
+    }
+
+    #[test]
+    fn next_departure_test() {
+        assert_eq!(
+            Some(Departure { time: 944, wait: 5 }),
+            next_departure(59, 939)
+        );
+        assert_eq!(
+            Some(Departure { time: 944, wait: 0 }),
+            next_departure(59, 944)
+        );
+        assert_eq!(Some(Departure { time: 0, wait: 0 }), next_departure(7, 0));
+        assert_eq!(None, next_departure(0, 939));
+    }
+
+    #[test]
+    fn timetable_test() {
+        let notes = parse("939\n7,13,x,x,59\n").unwrap();
+        assert_eq!(
+            Some((59, Departure { time: 944, wait: 5 })),
+            notes.earliest_bus()
+        );
+        let timetable = notes.timetable(939, 2);
+        assert_eq!(
+            vec![
+                BusTimetable {
+                    id: 7,
+                    departures: vec![
+                        Departure { time: 945, wait: 6 },
+                        Departure {
+                            time: 952,
+                            wait: 13
+                        }
+                    ]
+                },
+                BusTimetable {
+                    id: 13,
+                    departures: vec![
+                        Departure {
+                            time: 949,
+                            wait: 10
+                        },
+                        Departure {
+                            time: 962,
+                            wait: 23
+                        }
+                    ]
+                },
+                BusTimetable {
+                    id: 59,
+                    departures: vec![
+                        Departure { time: 944, wait: 5 },
+                        Departure {
+                            time: 1003,
+                            wait: 64
+                        }
+                    ]
+                },
+            ],
+            timetable
+        );
+    }
+
+    #[test]
+    fn solution_1_huge_timestamp_test() {
+        // Far too late to walk there minute by minute
+        let notes = Notes {
+            earliest_departure_minute: 10u128.pow(30) + 1,
+            buses: vec![Bus::Id(7), Bus::NotInService, Bus::Id(1_000_003)],
+        };
+        let (id, departure) = notes.earliest_bus().unwrap();
+        assert_eq!(7, id);
+        assert_eq!(0, departure.time % 7);
+        assert!(departure.wait < 7);
+    }
+
+    #[test]
+    fn earliest_timestamp_with_constraints_test() {
+        let notes = parse("939\n17,x,13\n").unwrap();
+        assert_eq!(Ok(BigInt::from(102)), notes.solution_2());
+        // 19 has to leave a minute after 13, which is the puzzle's 17,x,13,19
+        let extra = constraints::parse_constraints("19 = 13 + 1").unwrap();
+        assert_eq!(Ok(BigInt::from(3417)), notes.earliest_timestamp(&extra));
     }
 }
@@ -0,0 +1,336 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use super::crt::{self, Congruence, CrtError};
+use num::BigInt;
+
+// Every constraint is about when buses leave relative to one timestamp t:
+//
+//   17 at 0        bus 17 leaves at t
+//   19 at -3       bus 19 leaves 3 minutes before t
+//   13 = 17 + 5    bus 13 leaves 5 minutes after bus 17 does
+//   59 = 13 - 2    bus 59 leaves 2 minutes before bus 13 does
+//
+// Blank lines and anything after a `#` are ignored. Bus 0 would never leave, so isn't allowed.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Constraint {
+    At {
+        bus: u128,
+        offset: i128,
+    },
+    After {
+        bus: u128,
+        other: u128,
+        minutes: i128,
+    },
+}
+
+impl fmt::Display for Constraint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Constraint::At { bus, offset } => write!(f, "{} at {}", bus, offset),
+            Constraint::After {
+                bus,
+                other,
+                minutes,
+            } if *minutes < 0 => write!(f, "{} = {} - {}", bus, other, minutes.unsigned_abs()),
+            Constraint::After {
+                bus,
+                other,
+                minutes,
+            } => write!(f, "{} = {} + {}", bus, other, minutes),
+        }
+    }
+}
+
+impl FromStr for Constraint {
+    type Err = anyhow::Error;
+
+    // A few whitespace separated words, like the day 8 debugger's commands
+    fn from_str(s: &str) -> Result<Constraint> {
+        let bus_id = |word: &str| -> Result<u128> {
+            match word.parse()? {
+                0 => anyhow::bail!("there's no bus 0"),
+                id => Ok(id),
+            }
+        };
+        let words: Vec<_> = s.split_whitespace().collect();
+        let constraint = match words.as_slice() {
+            [bus, "at", offset] => Constraint::At {
+                bus: bus_id(bus)?,
+                offset: offset.parse()?,
+            },
+            [bus, "=", other, sign, minutes] => {
+                let minutes: i128 = minutes.parse()?;
+                let minutes = match *sign {
+                    "+" => minutes,
+                    "-" => minutes
+                        .checked_neg()
+                        .ok_or_else(|| anyhow::anyhow!("{} minutes is too many", minutes))?,
+                    other => anyhow::bail!("expected + or -, not [{}]", other),
+                };
+                Constraint::After {
+                    bus: bus_id(bus)?,
+                    other: bus_id(other)?,
+                    minutes,
+                }
+            }
+            _ => anyhow::bail!("expected `<bus> at <offset>` or `<bus> = <bus> +/- <minutes>`"),
+        };
+        Ok(constraint)
+    }
+}
+
+pub fn parse_constraints(s: &str) -> Result<Vec<Constraint>> {
+    s.lines()
+        .enumerate()
+        .filter_map(|(idx, line)| {
+            let without_comment = line.split('#').next().unwrap_or("").trim();
+            if without_comment.is_empty() {
+                None
+            } else {
+                Some(
+                    without_comment
+                        .parse()
+                        .with_context(|| format!("Line {}: could not parse [{}]", idx + 1, line)),
+                )
+            }
+        })
+        .collect()
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ScheduleError {
+    // Relative to another bus that's never pinned to t, directly or through other buses
+    Unanchored(Constraint),
+    // Puts its bus further from t than an i128 can hold, adding up the buses it's relative to
+    OffsetOverflow(Constraint),
+    Unsolvable(CrtError),
+}
+
+impl fmt::Display for ScheduleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScheduleError::Unanchored(constraint) => write!(
+                f,
+                "[{}] can't be tied back to t: give one of the buses it depends on an `at`",
+                constraint
+            ),
+            ScheduleError::OffsetOverflow(constraint) => write!(
+                f,
+                "[{}] puts its bus too far from t to keep track of",
+                constraint
+            ),
+            ScheduleError::Unsolvable(e) => write!(f, "the schedule can't be met: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ScheduleError {}
+
+impl From<CrtError> for ScheduleError {
+    fn from(e: CrtError) -> ScheduleError {
+        ScheduleError::Unsolvable(e)
+    }
+}
+
+// Turns every constraint into "bus leaves at t + offset", i.e. t ≡ -offset (mod bus). Relative
+// constraints take their offset from the first offset we found for the other bus.
+pub fn congruences(constraints: &[Constraint]) -> Result<Vec<Congruence>, ScheduleError> {
+    let mut offsets: HashMap<u128, i128> = HashMap::new();
+    let mut resolved = Vec::with_capacity(constraints.len());
+    let mut pending: Vec<_> = constraints.iter().collect();
+    let mut invalid = None;
+    loop {
+        let before = pending.len();
+        pending.retain(|constraint| {
+            let (bus, offset) = match constraint {
+                Constraint::At { bus, offset } => (*bus, *offset),
+                Constraint::After {
+                    bus,
+                    other,
+                    minutes,
+                } => match offsets
+                    .get(other)
+                    .map(|offset| offset.checked_add(*minutes))
+                {
+                    Some(Some(offset)) => (*bus, offset),
+                    Some(None) => {
+                        invalid = Some(ScheduleError::OffsetOverflow(**constraint));
+                        return false;
+                    }
+                    None => return true,
+                },
+            };
+            offsets.entry(bus).or_insert(offset);
+            match Congruence::new(-BigInt::from(offset), bus) {
+                Ok(congruence) => resolved.push(congruence),
+                Err(e) => invalid = Some(e.into()),
+            }
+            false
+        });
+        if let Some(e) = invalid {
+            return Err(e);
+        }
+        match pending.first() {
+            None => return Ok(resolved),
+            Some(constraint) if pending.len() == before => {
+                return Err(ScheduleError::Unanchored(**constraint))
+            }
+            _ => {}
+        }
+    }
+}
+
+// The earliest t >= 0 that meets every constraint
+pub fn solve(constraints: &[Constraint]) -> Result<BigInt, ScheduleError> {
+    Ok(crt::solve(&congruences(constraints)?)?.residue)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_test() {
+        let input = "17 at 0
+# bus 13 is always a bit late
+13 = 17 + 2   # comments can go at the end too
+
+19 at -3
+59 = 13 - 4
+";
+        assert_eq!(
+            vec![
+                Constraint::At { bus: 17, offset: 0 },
+                Constraint::After {
+                    bus: 13,
+                    other: 17,
+                    minutes: 2
+                },
+                Constraint::At {
+                    bus: 19,
+                    offset: -3
+                },
+                Constraint::After {
+                    bus: 59,
+                    other: 13,
+                    minutes: -4
+                },
+            ],
+            parse_constraints(input).unwrap()
+        );
+        let constraints = parse_constraints(input).unwrap();
+        let reprinted: Vec<_> = constraints.iter().map(|c| c.to_string()).collect();
+        assert_eq!(
+            vec!["17 at 0", "13 = 17 + 2", "19 at -3", "59 = 13 - 4"],
+            reprinted
+        );
+
+        let error = parse_constraints("17 at 0\n13 after 17\n").unwrap_err();
+        assert_eq!("Line 2: could not parse [13 after 17]", error.to_string());
+        assert!(parse_constraints("13 = 17 * 2").is_err());
+    }
+
+    #[test]
+    fn offset_overflow_test() {
+        let input = format!("17 at {}\n13 = 17 + 1\n", i128::MAX);
+        let constraints = parse_constraints(&input).unwrap();
+        let error = solve(&constraints).unwrap_err();
+        assert_eq!(
+            ScheduleError::OffsetOverflow(Constraint::After {
+                bus: 13,
+                other: 17,
+                minutes: 1
+            }),
+            error
+        );
+        assert_eq!(
+            "[13 = 17 + 1] puts its bus too far from t to keep track of",
+            error.to_string()
+        );
+
+        let input = format!("13 = 17 - {}", i128::MIN);
+        let error = parse_constraints(&input).unwrap_err();
+        assert_eq!(
+            format!("{} minutes is too many", i128::MIN),
+            error.root_cause().to_string()
+        );
+
+        // A chain that gets there a bit at a time
+        let step = i128::MAX / 3 + 1;
+        let input = format!(
+            "2 at 0\n3 = 2 + {step}\n5 = 3 + {step}\n7 = 5 + {step}\n",
+            step = step
+        );
+        let constraints = parse_constraints(&input).unwrap();
+        assert!(matches!(
+            solve(&constraints),
+            Err(ScheduleError::OffsetOverflow(Constraint::After {
+                bus: 7,
+                ..
+            }))
+        ));
+    }
+
+    #[test]
+    fn bus_0_test() {
+        for input in ["0 at 1", "13 = 0 + 5", "0 = 13 - 5"].iter() {
+            let error = parse_constraints(input).unwrap_err();
+            assert_eq!("there's no bus 0", error.root_cause().to_string());
+        }
+
+        // Built by hand rather than parsed, it's still an error rather than a divide by zero
+        assert_eq!(
+            Err(ScheduleError::Unsolvable(CrtError::NonPositiveModulus(
+                BigInt::from(0)
+            ))),
+            solve(&[Constraint::At { bus: 0, offset: 1 }])
+        );
+    }
+
+    #[test]
+    fn solve_test() {
+        // The puzzle's 17,x,13,19 written out as constraints, then with 13 and 19 relative to 17
+        let absolute = parse_constraints("17 at 0\n13 at 2\n19 at 3\n").unwrap();
+        assert_eq!(Ok(BigInt::from(3417)), solve(&absolute));
+        let relative = parse_constraints("19 = 13 + 1\n13 = 17 + 2\n17 at 0\n").unwrap();
+        assert_eq!(Ok(BigInt::from(3417)), solve(&relative));
+    }
+
+    #[test]
+    fn negative_offsets_test() {
+        // 7 leaves 2 minutes before t, 5 at t
+        let constraints = parse_constraints("5 at 0\n7 at -2\n").unwrap();
+        let t = solve(&constraints).unwrap();
+        assert_eq!(BigInt::from(30), t);
+        assert_eq!(BigInt::from(0), (&t - 2) % 7);
+    }
+
+    #[test]
+    fn errors_test() {
+        let constraints = parse_constraints("5 at 0\n7 = 11 + 1\n").unwrap();
+        assert_eq!(
+            Err(ScheduleError::Unanchored(Constraint::After {
+                bus: 7,
+                other: 11,
+                minutes: 1
+            })),
+            solve(&constraints)
+        );
+
+        // Bus 4 can't leave at both an even and an odd minute
+        let constraints = parse_constraints("4 at 0\n6 = 4 + 1\n").unwrap();
+        let error = solve(&constraints).unwrap_err();
+        assert!(matches!(
+            error,
+            ScheduleError::Unsolvable(CrtError::Inconsistent { .. })
+        ));
+        assert_eq!(
+            "the schedule can't be met: no number satisfies both x ≡ 0 (mod 4) and x ≡ 5 (mod 6)",
+            error.to_string()
+        );
+    }
+}
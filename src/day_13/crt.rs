@@ -0,0 +1,206 @@
+use num::{BigInt, Integer, One, Signed, Zero};
+use std::fmt;
+
+// x ≡ residue (mod modulus)
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Congruence {
+    pub residue: BigInt,
+    pub modulus: BigInt,
+}
+
+impl Congruence {
+    // Normalises the residue into 0..modulus, which has to be positive
+    pub fn new(
+        residue: impl Into<BigInt>,
+        modulus: impl Into<BigInt>,
+    ) -> Result<Congruence, CrtError> {
+        let modulus = modulus.into();
+        if !modulus.is_positive() {
+            return Err(CrtError::NonPositiveModulus(modulus));
+        }
+        Ok(Congruence {
+            residue: residue.into().mod_floor(&modulus),
+            modulus,
+        })
+    }
+
+    pub fn holds_for(&self, x: &BigInt) -> bool {
+        x.mod_floor(&self.modulus) == self.residue
+    }
+}
+
+impl fmt::Display for Congruence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "x ≡ {} (mod {})", self.residue, self.modulus)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum CrtError {
+    NonPositiveModulus(BigInt),
+    // `combined` is everything before `conflicting` folded together. Boxed to keep the error
+    // small, since BigInts aren't.
+    Inconsistent {
+        combined: Box<Congruence>,
+        conflicting: Box<Congruence>,
+    },
+}
+
+impl fmt::Display for CrtError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CrtError::NonPositiveModulus(modulus) => {
+                write!(f, "moduli have to be positive, not {}", modulus)
+            }
+            CrtError::Inconsistent {
+                combined,
+                conflicting,
+            } => write!(
+                f,
+                "no number satisfies both {} and {}",
+                combined, conflicting
+            ),
+        }
+    }
+}
+
+impl std::error::Error for CrtError {}
+
+// (g, x, y) such that a * x + b * y = g = gcd(a, b)
+fn extended_gcd(a: &BigInt, b: &BigInt) -> (BigInt, BigInt, BigInt) {
+    let (mut old_r, mut r) = (a.clone(), b.clone());
+    let (mut old_s, mut s) = (BigInt::one(), BigInt::zero());
+    let (mut old_t, mut t) = (BigInt::zero(), BigInt::one());
+    while !r.is_zero() {
+        let quotient = &old_r / &r;
+        let next_r = &old_r - &quotient * &r;
+        old_r = std::mem::replace(&mut r, next_r);
+        let next_s = &old_s - &quotient * &s;
+        old_s = std::mem::replace(&mut s, next_s);
+        let next_t = &old_t - &quotient * &t;
+        old_t = std::mem::replace(&mut t, next_t);
+    }
+    (old_r, old_s, old_t)
+}
+
+// Merges two congruences into one modulo the lcm of their moduli. The moduli don't need to be
+// coprime, but then the residues have to agree modulo their gcd.
+fn combine(a: &Congruence, b: &Congruence) -> Option<Congruence> {
+    let (gcd, p, _) = extended_gcd(&a.modulus, &b.modulus);
+    let difference = &b.residue - &a.residue;
+    if !difference.is_multiple_of(&gcd) {
+        return None;
+    }
+    // a.modulus * p ≡ gcd (mod b.modulus), so stepping a.residue on by a.modulus * k with
+    // k = difference / gcd * p lands on b.residue
+    let step_modulus = &b.modulus / &gcd;
+    let k = (difference / &gcd * p).mod_floor(&step_modulus);
+    let modulus = &a.modulus * step_modulus;
+    Some(Congruence {
+        residue: (&a.residue + &a.modulus * k).mod_floor(&modulus),
+        modulus,
+    })
+}
+
+// The single congruence that's equivalent to all of them together. Its residue is the smallest
+// non-negative number that satisfies every one.
+pub fn solve<'a>(
+    congruences: impl IntoIterator<Item = &'a Congruence>,
+) -> Result<Congruence, CrtError> {
+    congruences
+        .into_iter()
+        .try_fold(Congruence::new(0, 1)?, |combined, next| {
+            if !next.modulus.is_positive() {
+                return Err(CrtError::NonPositiveModulus(next.modulus.clone()));
+            }
+            combine(&combined, next).ok_or_else(|| CrtError::Inconsistent {
+                combined: Box::new(combined),
+                conflicting: Box::new(next.clone()),
+            })
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn congruence(residue: impl Into<BigInt>, modulus: impl Into<BigInt>) -> Congruence {
+        Congruence::new(residue, modulus).unwrap()
+    }
+
+    #[test]
+    fn extended_gcd_test() {
+        let (g, x, y) = extended_gcd(&BigInt::from(240), &BigInt::from(46));
+        assert_eq!(BigInt::from(2), g);
+        assert_eq!(g, BigInt::from(240) * x + BigInt::from(46) * y);
+    }
+
+    #[test]
+    fn coprime_test() {
+        let congruences = vec![congruence(2, 3), congruence(3, 5), congruence(2, 7)];
+        assert_eq!(Ok(congruence(23, 105)), solve(&congruences));
+    }
+
+    #[test]
+    fn non_coprime_test() {
+        let congruences = vec![congruence(2, 4), congruence(4, 6)];
+        assert_eq!(Ok(congruence(10, 12)), solve(&congruences));
+
+        // The second one's already implied by the first
+        let congruences = vec![congruence(5, 12), congruence(1, 4)];
+        assert_eq!(Ok(congruence(5, 12)), solve(&congruences));
+    }
+
+    #[test]
+    fn inconsistent_test() {
+        let congruences = vec![congruence(1, 5), congruence(1, 4), congruence(2, 6)];
+        let error = solve(&congruences).unwrap_err();
+        assert_eq!(
+            CrtError::Inconsistent {
+                combined: Box::new(congruence(1, 20)),
+                conflicting: Box::new(congruence(2, 6)),
+            },
+            error
+        );
+        assert_eq!(
+            "no number satisfies both x ≡ 1 (mod 20) and x ≡ 2 (mod 6)",
+            error.to_string()
+        );
+
+        assert_eq!(
+            Err(CrtError::NonPositiveModulus(BigInt::zero())),
+            Congruence::new(1, 0)
+        );
+        assert_eq!(
+            Err(CrtError::NonPositiveModulus(BigInt::from(-3))),
+            Congruence::new(1, -3)
+        );
+        assert_eq!(
+            Err(CrtError::NonPositiveModulus(BigInt::zero())),
+            solve(&[Congruence {
+                residue: BigInt::zero(),
+                modulus: BigInt::zero()
+            }])
+        );
+    }
+
+    #[test]
+    fn beyond_u128_test() {
+        // Mersenne primes 2^61 - 1, 2^89 - 1 and 2^107 - 1, whose product is way past u128
+        let primes: Vec<BigInt> = [61, 89, 107]
+            .iter()
+            .map(|p| (BigInt::one() << *p as usize) - 1)
+            .collect();
+        let congruences: Vec<_> = primes
+            .iter()
+            .enumerate()
+            .map(|(i, p)| congruence(-(i as i64) * 12345, p.clone()))
+            .collect();
+        let solution = solve(&congruences).unwrap();
+        let product: BigInt = primes.iter().product();
+        assert!(product > BigInt::from(u128::MAX));
+        assert_eq!(product, solution.modulus);
+        assert!(solution.residue < product);
+        assert!(congruences.iter().all(|c| c.holds_for(&solution.residue)));
+    }
+}
@@ -1,64 +1,120 @@
 use anyhow::Result;
 use combine::easy;
-use combine::lib::collections::HashMap;
 use combine::parser::char::*;
 use combine::*;
-use itertools::Itertools;
+use num::BigUint;
+use std::fmt;
 use std::num::ParseIntError;
 use std::result::Result as StdResult;
 
+use dump::{DumpFormat, Memory, WriteRecord};
+use floating::{AddressPattern, FloatingMemory};
+
+pub mod dump;
+pub mod floating;
+
 const INPUT: &str = include_str!("../data/day_14_input");
 
-pub fn run() -> Result<()> {
+// `dump` is the format to print version 1's final memory in, if any ("hex" or "json"). There's no
+// dump of version 2's, whose floating addresses can cover up to 2^64 addresses each.
+//
+// `width` is how many bits wide memory is, which masks can't be any longer than, if not the
+// puzzle's 36
+pub fn run(dump: Option<&str>, width: Option<u32>) -> Result<()> {
     println!("*** Day 14: Docking Data ***");
     println!("Input: {}", INPUT);
-    let groups = parse(INPUT)?;
-    let simulation_1 = Simulation::new(groups.clone());
-
-    println!("Solution 1: {:?}", simulation_1.solution_1());
+    let width = width.unwrap_or(DEFAULT_WIDTH);
+    let groups = parse_with_width(INPUT, width)?;
+    let simulation = Simulation::new(groups);
 
-    let simulation_2 = Simulation::new(groups);
-    println!("Solution 2: {:?}", simulation_2.solution_2());
+    if let Some(format) = dump {
+        let format: DumpFormat = format.parse()?;
+        print!("{}", simulation.run().export(format, width));
+    }
+    println!("Solution 1: {}", simulation.solution_1());
+    println!("Solution 2: {}", simulation.solution_2());
     Ok(())
 }
 
 #[derive(Debug, Eq, PartialEq)]
 struct Simulation {
-    memory_space: HashMap<usize, u64>,
     groups: Vec<Group>,
 }
 
 impl Simulation {
     fn new(groups: Vec<Group>) -> Simulation {
-        Simulation {
-            memory_space: HashMap::new(),
-            groups,
-        }
+        Simulation { groups }
     }
 
-    fn solution_1(mut self) -> u64 {
-        for group in self.groups.iter() {
-            for mem_set in group.mem_sets.iter() {
-                let mem_set_masked_value = group.mask.apply(mem_set.value);
-                self.memory_space.insert(mem_set.idx, mem_set_masked_value);
+    // Runs the version 1 decoder, remembering which mem set wrote what where
+    fn run(&self) -> Memory {
+        let mut memory = Memory::new();
+        for (group_idx, group) in self.groups.iter().enumerate() {
+            for (mem_set_idx, mem_set) in group.mem_sets.iter().enumerate() {
+                memory.write(
+                    mem_set.idx,
+                    WriteRecord {
+                        group: group_idx,
+                        mem_set: mem_set_idx,
+                        value: group.mask.apply(mem_set.value),
+                    },
+                );
             }
         }
-        self.memory_space.values().sum()
+        memory
+    }
+
+    fn solution_1(&self) -> BigUint {
+        self.run().sum()
     }
 
-    fn solution_2(mut self) -> u64 {
+    // Floating addresses can cover far too many addresses to write one by one
+    fn solution_2(&self) -> BigUint {
+        let mut memory = FloatingMemory::new();
         for group in self.groups.iter() {
             for mem_set in group.mem_sets.iter() {
-                let mem_addresses = group.mask.address_decode(mem_set.idx);
-                for mem_address in mem_addresses {
-                    self.memory_space.insert(mem_address, mem_set.value);
-                }
+                memory.write(group.mask.address_pattern(mem_set.idx), mem_set.value);
             }
         }
-        self.memory_space.values().sum()
+        memory.sum()
     }
 }
 
+// Masks in the puzzle are 36 bits wide, but any width that fits in the values works
+const DEFAULT_WIDTH: u32 = 36;
+const MAX_WIDTH: u32 = u64::BITS;
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+enum MaskError {
+    UnsupportedWidth(u32),
+    TooLong { length: usize, width: u32 },
+    InvalidCharacter { position: usize, found: char },
+}
+
+impl fmt::Display for MaskError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MaskError::UnsupportedWidth(width) => write!(
+                f,
+                "masks can be at most {} bits wide, not {}",
+                MAX_WIDTH, width
+            ),
+            MaskError::TooLong { length, width } => write!(
+                f,
+                "mask is {} bits long, but memory is only {} bits wide",
+                length, width
+            ),
+            MaskError::InvalidCharacter { position, found } => write!(
+                f,
+                "mask character {} is [{}], but should be X, 0 or 1",
+                position, found
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MaskError {}
+
 #[derive(Debug, Eq, PartialEq, Clone)]
 struct Mask {
     or_bitmask: u64,
@@ -68,100 +124,53 @@ struct Mask {
 }
 
 impl Mask {
-    fn from_str(s: &str) -> StdResult<Mask, ParseIntError> {
-        let (
-            mut or_bitmask_vec_36_bits,
-            mut and_bitmask_str_36_bits,
-            mut part_2_initial_or_mask_bits,
-            part_2_xor_masks,
-        ) = s.chars().enumerate().fold(
-            (vec!['0'; 36], vec!['1'; 36], vec!['0'; 36], vec![]),
-            |(
-                mut or_bitmask_str,
-                mut and_bitmask_str,
-                mut part_2_initial_or_mask,
-                mut part_2_xor_masks,
-            ),
-             (idx, next_char)| {
-                match next_char {
-                    '1' => {
-                        or_bitmask_str[idx] = '1';
-                        part_2_initial_or_mask[idx] = '1';
-                    }
-                    '0' => and_bitmask_str[idx] = '0',
-                    _ /* assume x only due to implementation */ => {
-                        part_2_initial_or_mask[idx] = '1';
-                        part_2_xor_masks.push(2u64.pow((s.len() - 1 - idx) as u32));
-                    }
-                }
-                (
-                    or_bitmask_str,
-                    and_bitmask_str,
-                    part_2_initial_or_mask,
-                    part_2_xor_masks,
-                )
-            },
-        );
-        let or_bitmask_string: String = {
-            let mut full_string = vec!['0'; 28];
-            full_string.append(&mut or_bitmask_vec_36_bits);
-            full_string.iter().collect()
-        };
-        let part_2_initial_or_mask_string: String = {
-            let mut full_string = vec!['0'; 28];
-            full_string.append(&mut part_2_initial_or_mask_bits);
-            full_string.iter().collect()
-        };
-        let and_bitmask_string: String = {
-            let mut full_string = vec!['1'; 28];
-            full_string.append(&mut and_bitmask_str_36_bits);
-            full_string.iter().collect()
+    // The last character of the mask is bit 0. Bits above the mask are left alone.
+    fn with_width(s: &str, width: u32) -> StdResult<Mask, MaskError> {
+        if width > MAX_WIDTH {
+            return Err(MaskError::UnsupportedWidth(width));
+        }
+        let length = s.chars().count();
+        if length > width as usize {
+            return Err(MaskError::TooLong { length, width });
+        }
+        let mut mask = Mask {
+            or_bitmask: 0,
+            and_bitmask: u64::MAX,
+            part_2_initial_or_bitmask: 0,
+            part_2_xor_masks: vec![],
         };
-
-        let or_bitmask = u64::from_str_radix(&or_bitmask_string, 2);
-        let and_bitmask = u64::from_str_radix(&and_bitmask_string, 2);
-        let part_2_initial_or_bitmask = u64::from_str_radix(&part_2_initial_or_mask_string, 2);
-        or_bitmask.and_then(|or_bitmask| {
-            and_bitmask.and_then(|and_bitmask| {
-                part_2_initial_or_bitmask.map(|part_2_initial_or_bitmask| Mask {
-                    or_bitmask,
-                    and_bitmask,
-                    part_2_initial_or_bitmask,
-                    part_2_xor_masks,
-                })
-            })
-        })
+        for (position, c) in s.chars().enumerate() {
+            let bit = 1 << (length - 1 - position);
+            match c {
+                '1' => {
+                    mask.or_bitmask |= bit;
+                    mask.part_2_initial_or_bitmask |= bit;
+                }
+                '0' => mask.and_bitmask &= !bit,
+                'X' => {
+                    mask.part_2_initial_or_bitmask |= bit;
+                    mask.part_2_xor_masks.push(bit);
+                }
+                found => return Err(MaskError::InvalidCharacter { position, found }),
+            }
+        }
+        Ok(mask)
     }
 
     fn apply(&self, i: u64) -> u64 {
         (i | self.or_bitmask) & self.and_bitmask
     }
 
-    fn address_decode(&self, address: usize) -> Vec<usize> {
-        let initial_ored_value = address as u64 | self.part_2_initial_or_bitmask;
-        let xor_masks = (1..self.part_2_xor_masks.len() + 1)
-            .into_iter()
-            .flat_map(|xor_combo_length| {
-                let xor_masks_at_length: Vec<_> = self
-                    .part_2_xor_masks
-                    .iter()
-                    .combinations(xor_combo_length)
-                    .map(|combination| combination.iter().fold(0, |acc, next| acc ^ **next))
-                    .collect();
-                xor_masks_at_length
-            })
-            .unique();
-        let mut xored_combinations: Vec<_> = xor_masks
-            .map(|or_mask| (initial_ored_value ^ or_mask) as usize)
-            .collect();
-        xored_combinations.push(initial_ored_value as usize);
-        xored_combinations
+    // The version 2 decoder: 1s overwrite, 0s leave the address alone, and Xs float
+    fn address_pattern(&self, address: u64) -> AddressPattern {
+        let floating = self.part_2_xor_masks.iter().fold(0, |acc, bit| acc | bit);
+        AddressPattern::new(address | self.part_2_initial_or_bitmask, floating)
     }
 }
 
 #[derive(Debug, Eq, PartialEq, Clone)]
 struct MemSet {
-    idx: usize,
+    idx: u64,
     value: u64,
 }
 
@@ -172,13 +181,14 @@ struct Group {
     mem_sets: Vec<MemSet>,
 }
 
-fn parse(s: &str) -> StdResult<Vec<Group>, easy::ParseError<&str>> {
+fn parse_with_width(s: &str, width: u32) -> StdResult<Vec<Group>, easy::ParseError<&str>> {
+    // Take the whole word so that Mask can say what's wrong with it
     let mask_parser = attempt(string("mask"))
         .with(spaces())
         .with(char('='))
         .with(spaces())
-        .with(many::<String, _, _>(one_of("X01".chars())))
-        .and_then(|s| Mask::from_str(&s));
+        .with(many1::<String, _, _>(satisfy(|c: char| !c.is_whitespace())))
+        .and_then(move |s| Mask::with_width(&s, width));
 
     let mem_set_parser = attempt(string("mem["))
         .with(idx_parser())
@@ -195,7 +205,7 @@ fn parse(s: &str) -> StdResult<Vec<Group>, easy::ParseError<&str>> {
     Ok(r)
 }
 
-fn idx_parser<Input>() -> impl Parser<Input, Output = usize>
+fn idx_parser<Input>() -> impl Parser<Input, Output = u64>
 where
     Input: Stream<Token = char>,
     Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
@@ -215,7 +225,7 @@ where
         <Input as combine::StreamOnce>::Position,
     >,
 {
-    many::<String, _, _>(digit()).and_then(|d| d.parse::<usize>())
+    many::<String, _, _>(digit()).and_then(|d| d.parse::<u64>())
 }
 
 fn value_parser<Input>() -> impl Parser<Input, Output = u64>
@@ -245,9 +255,13 @@ where
 mod tests {
     use super::*;
 
+    fn parse(s: &str) -> StdResult<Vec<Group>, easy::ParseError<&str>> {
+        parse_with_width(s, DEFAULT_WIDTH)
+    }
+
     #[test]
     fn mask_test() {
-        let m = Mask::from_str("XXXXXXXXXXXXXXXXXXXXXXXXXXXXX1XXXX0X").unwrap();
+        let m = Mask::with_width("XXXXXXXXXXXXXXXXXXXXXXXXXXXXX1XXXX0X", DEFAULT_WIDTH).unwrap();
         let r1 = m.apply(11);
         assert_eq!(73, r1);
         let r2 = m.apply(101);
@@ -258,8 +272,8 @@ mod tests {
 
     #[test]
     fn mask_address_decode_1_test() {
-        let m = Mask::from_str("000000000000000000000000000000X1001X").unwrap();
-        let mut r = m.address_decode(42);
+        let m = Mask::with_width("000000000000000000000000000000X1001X", DEFAULT_WIDTH).unwrap();
+        let mut r: Vec<_> = m.address_pattern(42).addresses().collect();
         r.sort();
         assert_eq!(4, r.len());
         assert_eq!(vec![26, 27, 58, 59], r);
@@ -267,8 +281,8 @@ mod tests {
 
     #[test]
     fn mask_address_decode_2_test() {
-        let m = Mask::from_str("00000000000000000000000000000000X0XX").unwrap();
-        let mut r = m.address_decode(26);
+        let m = Mask::with_width("00000000000000000000000000000000X0XX", DEFAULT_WIDTH).unwrap();
+        let mut r: Vec<_> = m.address_pattern(26).addresses().collect();
         r.sort();
         assert_eq!(8, r.len());
         assert_eq!(vec![16, 17, 18, 19, 24, 25, 26, 27], r);
@@ -431,6 +445,114 @@ mem[8] = 0
 ";
         let groups = parse(input).unwrap();
         let simulation = Simulation::new(groups);
-        assert_eq!(165, simulation.solution_1());
+        assert_eq!(BigUint::from(165u32), simulation.solution_1());
+    }
+
+    #[test]
+    fn solution_1_64_bit_test() {
+        // Every value comes out as all ones, and two of them don't fit in a u64
+        let input = format!("mask = {}\nmem[1] = 0\nmem[2] = 5\n", "1".repeat(64));
+        let groups = parse_with_width(&input, 64).unwrap();
+        let simulation = Simulation::new(groups);
+        assert_eq!(BigUint::from(u64::MAX) * 2u32, simulation.solution_1());
+    }
+
+    #[test]
+    fn mask_widths_test() {
+        let m = Mask::with_width("1X0", 8).unwrap();
+        assert_eq!(0b100, m.or_bitmask);
+        assert_eq!(!0b001, m.and_bitmask);
+        assert_eq!(0b110, m.part_2_initial_or_bitmask);
+        assert_eq!(vec![0b010], m.part_2_xor_masks);
+        assert_eq!(0b1111_1110, m.apply(0b1111_1011));
+
+        let full = "1".repeat(64);
+        assert_eq!(u64::MAX, Mask::with_width(&full, 64).unwrap().apply(0));
+        assert_eq!(
+            Err(MaskError::UnsupportedWidth(128)),
+            Mask::with_width("1", 128)
+        );
+    }
+
+    #[test]
+    fn mask_errors_test() {
+        assert_eq!(
+            Err(MaskError::TooLong {
+                length: 4,
+                width: 3
+            }),
+            Mask::with_width("X10X", 3)
+        );
+        assert_eq!(
+            Err(MaskError::InvalidCharacter {
+                position: 2,
+                found: 'x'
+            }),
+            Mask::with_width("10x1", DEFAULT_WIDTH)
+        );
+
+        let error = parse("mask = 1Y01\nmem[8] = 11\n").unwrap_err().to_string();
+        assert!(
+            error.contains("mask character 1 is [Y], but should be X, 0 or 1"),
+            "{}",
+            error
+        );
+        let error = parse_with_width("mask = 1X01X\nmem[8] = 11\n", 4)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.contains("mask is 5 bits long, but memory is only 4 bits wide"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn solution_2_test() {
+        let input = "mask = 000000000000000000000000000000X1001X
+mem[42] = 100
+mask = 00000000000000000000000000000000X0XX
+mem[26] = 1
+";
+        let groups = parse(input).unwrap();
+        assert_eq!(BigUint::from(208u32), Simulation::new(groups).solution_2());
+
+        // 2^36 addresses, all written at once
+        let groups = parse(&format!("mask = {}\nmem[0] = 5\n", "X".repeat(36))).unwrap();
+        assert_eq!(
+            BigUint::from(5u64 << 36),
+            Simulation::new(groups).solution_2()
+        );
+    }
+
+    #[test]
+    fn run_test() {
+        let input = "mask = XXXXXXXXXXXXXXXXXXXXXXXXXXXXX1XXXX0X
+mem[8] = 11
+mem[7] = 101
+mask = XXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXX
+mem[8] = 0
+";
+        let simulation = Simulation::new(parse(input).unwrap());
+        let memory = simulation.run();
+        assert_eq!(vec![(7, 101), (8, 0)], memory.dump().collect::<Vec<_>>());
+        assert_eq!(
+            vec![
+                WriteRecord {
+                    group: 0,
+                    mem_set: 0,
+                    value: 73
+                },
+                WriteRecord {
+                    group: 1,
+                    mem_set: 0,
+                    value: 0
+                }
+            ],
+            memory.history(8)
+        );
+        // Running again doesn't need a fresh simulation
+        assert_eq!(BigUint::from(101u32), simulation.solution_1());
+        assert_eq!(memory, simulation.run());
     }
 }
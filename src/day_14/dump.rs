@@ -0,0 +1,208 @@
+use num::BigUint;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+// Which mem set wrote a value: the `mem_set`th one in the `group`th mask group
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct WriteRecord {
+    pub group: usize,
+    pub mem_set: usize,
+    pub value: u64,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum DumpFormat {
+    Hex,
+    Json,
+}
+
+impl std::str::FromStr for DumpFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<DumpFormat> {
+        match s {
+            "hex" => Ok(DumpFormat::Hex),
+            "json" => Ok(DumpFormat::Json),
+            other => anyhow::bail!("unknown dump format [{}], expected hex or json", other),
+        }
+    }
+}
+
+// Memory with every write that ever went to each address, in order, kept sorted by address
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Memory {
+    history: BTreeMap<u64, Vec<WriteRecord>>,
+}
+
+impl Memory {
+    pub fn new() -> Memory {
+        Memory::default()
+    }
+
+    pub fn write(&mut self, address: u64, record: WriteRecord) {
+        self.history.entry(address).or_default().push(record);
+    }
+
+    pub fn read(&self, address: u64) -> Option<u64> {
+        self.history
+            .get(&address)
+            .and_then(|writes| writes.last())
+            .map(|write| write.value)
+    }
+
+    pub fn history(&self, address: u64) -> &[WriteRecord] {
+        self.history
+            .get(&address)
+            .map(|writes| writes.as_slice())
+            .unwrap_or(&[])
+    }
+
+    // Every address that's been written, with what's there now, lowest address first
+    pub fn dump(&self) -> impl Iterator<Item = (u64, u64)> + '_ {
+        self.history
+            .iter()
+            .filter_map(|(address, writes)| Some((*address, writes.last()?.value)))
+    }
+
+    // With 64 bit values, a few of them are enough to go past a u64
+    pub fn sum(&self) -> BigUint {
+        self.dump().map(|(_, value)| BigUint::from(value)).sum()
+    }
+
+    pub fn export(&self, format: DumpFormat, width: u32) -> String {
+        match format {
+            DumpFormat::Hex => self.to_hex(width),
+            DumpFormat::Json => self.to_json(),
+        }
+    }
+
+    // One `address: value` line per address, zero padded to `width` bits so dumps line up for
+    // diffing
+    pub fn to_hex(&self, width: u32) -> String {
+        let digits = width.div_ceil(4) as usize;
+        let mut hex = String::new();
+        for (address, value) in self.dump() {
+            // Writing to a String can't fail
+            let _ = writeln!(
+                hex,
+                "{:0digits$x}: {:0digits$x}",
+                address,
+                value,
+                digits = digits
+            );
+        }
+        hex
+    }
+
+    // Addresses are strings since JSON keys have to be, one address per line
+    pub fn to_json(&self) -> String {
+        let entries: Vec<_> = self
+            .history
+            .iter()
+            .filter_map(|(address, writes)| {
+                let history: Vec<_> = writes
+                    .iter()
+                    .map(|w| {
+                        format!(
+                            r#"{{"group": {}, "mem_set": {}, "value": {}}}"#,
+                            w.group, w.mem_set, w.value
+                        )
+                    })
+                    .collect();
+                Some(format!(
+                    r#"  "{}": {{"value": {}, "history": [{}]}}"#,
+                    address,
+                    writes.last()?.value,
+                    history.join(", ")
+                ))
+            })
+            .collect();
+        if entries.is_empty() {
+            "{}\n".to_string()
+        } else {
+            format!("{{\n{}\n}}\n", entries.join(",\n"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn memory() -> Memory {
+        let mut memory = Memory::new();
+        let writes = vec![(8, 0, 0, 73), (7, 0, 1, 101), (8, 0, 2, 64), (3, 1, 0, 255)];
+        for (address, group, mem_set, value) in writes {
+            memory.write(
+                address,
+                WriteRecord {
+                    group,
+                    mem_set,
+                    value,
+                },
+            );
+        }
+        memory
+    }
+
+    #[test]
+    fn dump_and_history_test() {
+        let memory = memory();
+        assert_eq!(
+            vec![(3, 255), (7, 101), (8, 64)],
+            memory.dump().collect::<Vec<_>>()
+        );
+        assert_eq!(Some(64), memory.read(8));
+        assert_eq!(None, memory.read(9));
+        assert_eq!(
+            vec![0, 2],
+            memory
+                .history(8)
+                .iter()
+                .map(|w| w.mem_set)
+                .collect::<Vec<_>>()
+        );
+        assert!(memory.history(9).is_empty());
+        assert_eq!(BigUint::from(420u32), memory.sum());
+    }
+
+    #[test]
+    fn sum_past_u64_test() {
+        let mut memory = Memory::new();
+        for address in 0..3 {
+            memory.write(
+                address,
+                WriteRecord {
+                    group: 0,
+                    mem_set: address as usize,
+                    value: u64::MAX,
+                },
+            );
+        }
+        assert_eq!(BigUint::from(u64::MAX) * 3u32, memory.sum());
+    }
+
+    #[test]
+    fn export_test() {
+        let memory = memory();
+        assert_eq!(
+            "000000003: 0000000ff\n000000007: 000000065\n000000008: 000000040\n",
+            memory.export(DumpFormat::Hex, 36)
+        );
+        assert_eq!(
+            r#"{
+  "3": {"value": 255, "history": [{"group": 1, "mem_set": 0, "value": 255}]},
+  "7": {"value": 101, "history": [{"group": 0, "mem_set": 1, "value": 101}]},
+  "8": {"value": 64, "history": [{"group": 0, "mem_set": 0, "value": 73}, {"group": 0, "mem_set": 2, "value": 64}]}
+}
+"#,
+            memory.export(DumpFormat::Json, 36)
+        );
+        assert_eq!("{}\n", Memory::new().to_json());
+        assert_eq!(
+            Ok(DumpFormat::Json),
+            "json".parse::<DumpFormat>().map_err(|_| ())
+        );
+        assert!("xml".parse::<DumpFormat>().is_err());
+    }
+}
@@ -0,0 +1,195 @@
+use num::BigUint;
+
+// A set of addresses where some bits are fixed and the rest can be anything, like a version 2
+// mask applied to an address. Floating bits are always 0 in `fixed`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub struct AddressPattern {
+    pub fixed: u64,
+    pub floating: u64,
+}
+
+impl AddressPattern {
+    pub fn new(fixed: u64, floating: u64) -> AddressPattern {
+        AddressPattern {
+            fixed: fixed & !floating,
+            floating,
+        }
+    }
+
+    // How many addresses there are: up to 2^64, when everything floats
+    pub fn size(&self) -> u128 {
+        1 << self.floating.count_ones()
+    }
+
+    pub fn contains(&self, address: u64) -> bool {
+        address & !self.floating == self.fixed
+    }
+
+    // Whether some address is in both: they can only miss each other on a bit fixed in both
+    pub fn overlaps(&self, other: &AddressPattern) -> bool {
+        let fixed_in_both = !self.floating & !other.floating;
+        (self.fixed ^ other.fixed) & fixed_in_both == 0
+    }
+
+    // The addresses in `self` but not in `other`, as patterns that don't overlap each other.
+    //
+    // Each bit that floats here but is fixed in `other` splits off the half of what's left that
+    // has the other value for that bit, which can't be in `other`. Whatever's left at the end is
+    // entirely inside `other`.
+    pub fn subtract(&self, other: &AddressPattern) -> Vec<AddressPattern> {
+        if !self.overlaps(other) {
+            return vec![*self];
+        }
+        let mut rest = *self;
+        let mut pieces = Vec::new();
+        let mut splitting = self.floating & !other.floating;
+        while splitting != 0 {
+            let bit = splitting & splitting.wrapping_neg();
+            splitting &= !bit;
+            rest.floating &= !bit;
+            pieces.push(AddressPattern {
+                fixed: rest.fixed | (!other.fixed & bit),
+                floating: rest.floating,
+            });
+            rest.fixed |= other.fixed & bit;
+        }
+        pieces
+    }
+
+    // Every address, for when there aren't too many of them
+    pub fn addresses(&self) -> impl Iterator<Item = u64> + '_ {
+        // Counting up through the subsets of the floating bits
+        let mut subset = Some(0u64);
+        std::iter::from_fn(move || {
+            let current = subset?;
+            let next = current.wrapping_sub(self.floating) & self.floating;
+            subset = if next == 0 { None } else { Some(next) };
+            Some(self.fixed | current)
+        })
+    }
+}
+
+// Memory written through address patterns, without expanding them into addresses. Writes never
+// overlap: a new write carves its addresses out of the ones already there first.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct FloatingMemory {
+    writes: Vec<(AddressPattern, u64)>,
+}
+
+impl FloatingMemory {
+    pub fn new() -> FloatingMemory {
+        FloatingMemory::default()
+    }
+
+    pub fn write(&mut self, pattern: AddressPattern, value: u64) {
+        let writes = std::mem::take(&mut self.writes);
+        for (existing, existing_value) in writes {
+            self.writes.extend(
+                existing
+                    .subtract(&pattern)
+                    .into_iter()
+                    .map(|piece| (piece, existing_value)),
+            );
+        }
+        self.writes.push((pattern, value));
+    }
+
+    pub fn read(&self, address: u64) -> Option<u64> {
+        self.writes
+            .iter()
+            .find(|(pattern, _)| pattern.contains(address))
+            .map(|(_, value)| *value)
+    }
+
+    // How many disjoint patterns it takes to describe what's been written
+    pub fn patterns(&self) -> usize {
+        self.writes.len()
+    }
+
+    // Each write can cover up to 2^64 addresses, so this goes well past a u64
+    pub fn sum(&self) -> BigUint {
+        self.writes
+            .iter()
+            .map(|(pattern, value)| BigUint::from(pattern.size()) * *value)
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::{HashMap, HashSet};
+
+    #[test]
+    fn addresses_test() {
+        let pattern = AddressPattern::new(0b1010, 0b0101);
+        let addresses: HashSet<_> = pattern.addresses().collect();
+        let expected: HashSet<_> = vec![0b1010, 0b1011, 0b1110, 0b1111].into_iter().collect();
+        assert_eq!(expected, addresses);
+        assert_eq!(4, pattern.size());
+        assert_eq!(
+            vec![7],
+            AddressPattern::new(7, 0).addresses().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn subtract_test() {
+        // 1X0X minus XX00 leaves 1X01
+        let a = AddressPattern::new(0b1000, 0b0101);
+        let b = AddressPattern::new(0b0000, 0b1100);
+        let rest = a.subtract(&b);
+        let remaining: HashSet<_> = rest.iter().flat_map(|p| p.addresses()).collect();
+        let expected: HashSet<_> = vec![0b1001, 0b1101].into_iter().collect();
+        assert_eq!(expected, remaining);
+
+        // Disjoint, so nothing changes
+        let c = AddressPattern::new(0b0010, 0b0000);
+        assert_eq!(vec![a], a.subtract(&c));
+        // Entirely covered
+        assert!(b.subtract(&AddressPattern::new(0, 0b1111)).is_empty());
+    }
+
+    #[test]
+    fn matches_expanded_memory_test() {
+        let writes = vec![
+            (AddressPattern::new(0b0_1010, 0b1_0101), 3),
+            (AddressPattern::new(0b0_0000, 0b0_0111), 5),
+            (AddressPattern::new(0b1_1111, 0b0_0000), 11),
+            (AddressPattern::new(0b0_0100, 0b1_1001), 13),
+            (AddressPattern::new(0b0_0000, 0b1_1111), 2),
+            (AddressPattern::new(0b0_0011, 0b0_1000), 17),
+        ];
+        let mut memory = FloatingMemory::new();
+        let mut expanded = HashMap::new();
+        for (pattern, value) in writes {
+            memory.write(pattern, value);
+            for address in pattern.addresses() {
+                expanded.insert(address, value);
+            }
+            assert_eq!(
+                BigUint::from(expanded.values().map(|v| *v as u128).sum::<u128>()),
+                memory.sum()
+            );
+            for (address, value) in expanded.iter() {
+                assert_eq!(Some(*value), memory.read(*address));
+            }
+        }
+    }
+
+    #[test]
+    fn everything_floating_test() {
+        let mut memory = FloatingMemory::new();
+        memory.write(AddressPattern::new(0, u64::MAX), 3);
+        memory.write(AddressPattern::new(0, (1 << 40) - 1), 1);
+        assert_eq!(
+            BigUint::from(3 * (1u128 << 64) - 2 * (1 << 40)),
+            memory.sum()
+        );
+
+        let mut memory = FloatingMemory::new();
+        memory.write(AddressPattern::new(0, u64::MAX), u64::MAX);
+        let expected = (BigUint::from(1u32) << 64) * u64::MAX;
+        assert_eq!(expected, memory.sum());
+    }
+}
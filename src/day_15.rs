@@ -1,18 +1,52 @@
 use anyhow::Result;
 use combine::lib::collections::HashMap;
+use std::path::Path;
+
+mod checkpoint;
 
 const INPUT: &str = include_str!("../data/day_15_input");
 
 pub fn run() -> Result<()> {
     println!("*** Day 15: Rambunctious Recitation ***");
     println!("Input: {}", INPUT);
-    let game = parse(INPUT)?;
+    let mut game = parse(INPUT)?;
 
-    println!("Solution 1: {:?}", game.clone().nth_number(2020));
+    let solution_1 = game.nth_number(2020);
+    println!("Solution 1: {:?}", solution_1);
+    println!("Zeros by turn 2020: {}", game.zeros());
+    if let Some(number) = solution_1 {
+        println!(
+            "{} was first spoken on turn {:?}",
+            number,
+            game.first_occurrence(number, 2020)
+        );
+    }
 
-    // totally brute force
+    // Carries on from turn 2020
     println!("Solution 2: {:?}", game.nth_number(30000000));
+    println!("Largest gap: {:?}", game.largest_gap());
+
+    Ok(())
+}
 
+// Plays until turn `turns`, starting from the checkpoint in `path` if there is one, and saves
+// where it got to back there, so long games can be played a bit at a time
+pub fn run_checkpointed(path: &Path, turns: usize) -> Result<()> {
+    println!("*** Day 15: Rambunctious Recitation (checkpointed) ***");
+    let mut game = if path.exists() {
+        Game::load(path)?
+    } else {
+        parse(INPUT)?
+    };
+    println!("Resuming after turn {}", game.turns);
+    match game.nth_number(turns) {
+        Some(number) => println!("Turn {}: {}", turns, number),
+        None => println!("Already past turn {}", turns),
+    }
+    println!("Zeros so far: {}", game.zeros());
+    println!("Largest gap: {:?}", game.largest_gap());
+    game.save(path)?;
+    println!("Saved after turn {} to {}", game.turns, path.display());
     Ok(())
 }
 
@@ -26,60 +60,147 @@ fn parse(s: &str) -> Result<Game> {
     Ok(Game::new(nums))
 }
 
-#[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
-struct SpokenLast(usize);
+// Numbers below this get a slot in a Vec, which is much quicker than hashing. Numbers spoken
+// can get as big as the turn count, so the Vec only grows as far as the numbers we actually see.
+const DEFAULT_DENSE_LIMIT: usize = 1 << 26;
 
+// The Van Eck style sequence, starting with the initial numbers. Iterating speaks one number per
+// turn, so `take`, `skip` and `nth` pick up wherever the game got to.
 #[derive(Debug, PartialEq, Clone)]
 struct Game {
-    numbers_to_spoken_on: HashMap<usize, SpokenLast>,
+    // The turn (0 based) each number was last spoken on plus 1, with 0 meaning never
+    dense_spoken_on: Vec<u32>,
+    dense_limit: usize,
+    // Numbers too big for the Vec, and everything once turns no longer fit in a u32
+    sparse_spoken_on: HashMap<usize, usize>,
 
     initial_numbers: Vec<usize>,
     last_number: Option<usize>,
-    current_idx: usize,
+    // How many numbers have been spoken so far
+    turns: usize,
+
+    // Running totals, so they're there however far we've got
+    zeros: usize,
+    largest_gap: Option<Gap>,
+}
+
+// `number` came round again on `turn` (counting from 1), `length` turns after it was last spoken.
+// `length` is also the number spoken on the turn after.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+struct Gap {
+    number: usize,
+    turn: usize,
+    length: usize,
 }
 
 impl Game {
     fn new(initial_numbers: Vec<usize>) -> Game {
-        let init_len = initial_numbers.len();
-        let last_init_num = initial_numbers.last().copied();
-        let mut numbers_to_spoken_on = HashMap::new();
-        for (spoken_on_idx, num) in initial_numbers.iter().enumerate() {
-            numbers_to_spoken_on.insert(*num, SpokenLast(spoken_on_idx));
-        }
         Game {
-            numbers_to_spoken_on,
+            dense_spoken_on: Vec::new(),
+            dense_limit: DEFAULT_DENSE_LIMIT,
+            sparse_spoken_on: HashMap::new(),
             initial_numbers,
-            current_idx: init_len - 1,
-            last_number: last_init_num,
+            last_number: None,
+            turns: 0,
+            zeros: 0,
+            largest_gap: None,
         }
     }
 
-    fn step(&mut self) -> Option<usize> {
-        let last_num = self.last_number?;
-        let last_turn = self.current_idx;
-        self.current_idx += 1;
-        let result = if let Some(last_spoken) = self.numbers_to_spoken_on.get(&last_num).copied() {
-            last_turn - last_spoken.0
-        } else {
-            0
-        };
+    // How many of the numbers spoken so far were 0
+    fn zeros(&self) -> usize {
+        self.zeros
+    }
+
+    // The longest a number has gone between being spoken, out of the turns so far. Ties go to
+    // the earlier one.
+    fn largest_gap(&self) -> Option<Gap> {
+        self.largest_gap
+    }
+
+    // The turn `number` was first spoken on, looking no further than turn `give_up_after`. We
+    // only remember when numbers were last spoken, so this plays again from the start.
+    fn first_occurrence(&self, number: usize, give_up_after: usize) -> Option<usize> {
+        Game::new(self.initial_numbers.clone())
+            .with_dense_limit(self.dense_limit)
+            .take(give_up_after)
+            .position(|n| n == number)
+            .map(|idx| idx + 1)
+    }
+
+    fn with_dense_limit(mut self, dense_limit: usize) -> Game {
+        self.dense_limit = dense_limit;
+        self
+    }
 
-        self.last_number = Some(result);
+    fn last_spoken_on(&self, number: usize) -> Option<usize> {
+        match self.dense_spoken_on.get(number) {
+            Some(0) | None => self.sparse_spoken_on.get(&number).copied(),
+            Some(turn) => Some(*turn as usize - 1),
+        }
+    }
 
-        self.numbers_to_spoken_on
-            .insert(last_num, SpokenLast(last_turn));
-        Some(result)
+    fn remember(&mut self, number: usize, turn: usize) {
+        if number < self.dense_limit && turn < u32::MAX as usize {
+            if number >= self.dense_spoken_on.len() {
+                let len = (number + 1)
+                    .max(self.dense_spoken_on.len() * 2)
+                    .min(self.dense_limit);
+                self.dense_spoken_on.resize(len, 0);
+            }
+            self.dense_spoken_on[number] = turn as u32 + 1;
+        } else {
+            if let Some(slot) = self.dense_spoken_on.get_mut(number) {
+                *slot = 0;
+            }
+            self.sparse_spoken_on.insert(number, turn);
+        }
     }
 
-    fn nth_number(mut self, n: usize) -> Option<usize> {
-        if n <= self.current_idx {
+    // The number spoken on turn `n` (counting from 1), as long as we haven't gone past it
+    fn nth_number(&mut self, n: usize) -> Option<usize> {
+        if n <= self.turns {
             None
         } else {
-            while self.current_idx < n - 2 {
-                self.step();
+            self.nth(n - self.turns - 1)
+        }
+    }
+}
+
+impl Iterator for Game {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        let turn = self.turns;
+        let number = match self.initial_numbers.get(turn) {
+            Some(initial) => *initial,
+            None => {
+                let last_number = self.last_number?;
+                match self.last_spoken_on(last_number) {
+                    Some(spoken_on) => {
+                        let length = turn - 1 - spoken_on;
+                        if self.largest_gap.map_or(true, |gap| length > gap.length) {
+                            self.largest_gap = Some(Gap {
+                                number: last_number,
+                                turn,
+                                length,
+                            });
+                        }
+                        length
+                    }
+                    None => 0,
+                }
             }
-            self.step()
+        };
+        if number == 0 {
+            self.zeros += 1;
+        }
+        if let Some(last_number) = self.last_number {
+            self.remember(last_number, turn - 1);
         }
+        self.last_number = Some(number);
+        self.turns += 1;
+        Some(number)
     }
 }
 
@@ -96,8 +217,9 @@ mod tests {
     #[test]
     fn step_test() {
         let mut game = Game::new(vec![0, 3, 6]);
+        assert_eq!(vec![0, 3, 6], game.by_ref().take(3).collect::<Vec<_>>());
         for expected in vec![0, 3, 3, 1, 0, 4, 0] {
-            let r = game.step().unwrap();
+            let r = game.next().unwrap();
             assert_eq!(expected, r);
         }
     }
@@ -119,4 +241,80 @@ mod tests {
         assert_eq!(438, Game::new(vec![3, 2, 1]).nth_number(2020).unwrap());
         assert_eq!(1836, Game::new(vec![3, 1, 2]).nth_number(2020).unwrap());
     }
+
+    #[test]
+    fn iterator_test() {
+        let mut game = Game::new(vec![0, 3, 6]);
+        assert_eq!(
+            vec![0, 3, 6, 0, 3, 3, 1, 0, 4, 0],
+            game.by_ref().take(10).collect::<Vec<_>>()
+        );
+        // Picks up at turn 11 rather than starting again
+        assert_eq!(Some(2), game.next());
+        assert_eq!(Some(436), game.nth_number(2020));
+        assert_eq!(None, game.nth_number(2020));
+        assert_eq!(Some(436), Game::new(vec![0, 3, 6]).nth(2019));
+        assert_eq!(None, Game::new(vec![]).next());
+    }
+
+    #[test]
+    fn sparse_numbers_test() {
+        // Everything's bigger than the dense limit, or close to it
+        let dense: Vec<_> = Game::new(vec![7, 12, 1, 0]).take(5000).collect();
+        let sparse: Vec<_> = Game::new(vec![7, 12, 1, 0])
+            .with_dense_limit(10)
+            .take(5000)
+            .collect();
+        let hashed: Vec<_> = Game::new(vec![7, 12, 1, 0])
+            .with_dense_limit(0)
+            .take(5000)
+            .collect();
+        assert_eq!(dense, sparse);
+        assert_eq!(dense, hashed);
+    }
+
+    #[test]
+    fn analytics_test() {
+        // 0, 3, 6, 0, 3, 3, 1, 0, 4, 0
+        let mut game = Game::new(vec![0, 3, 6]);
+        assert_eq!(None, game.largest_gap());
+        assert_eq!(Some(0), game.nth_number(10));
+        assert_eq!(4, game.zeros());
+        assert_eq!(
+            Some(Gap {
+                number: 0,
+                turn: 8,
+                length: 4
+            }),
+            game.largest_gap()
+        );
+        assert_eq!(Some(7), game.first_occurrence(1, 100));
+        assert_eq!(Some(1), game.first_occurrence(0, 100));
+        assert_eq!(None, game.first_occurrence(1, 6));
+        assert_eq!(None, game.first_occurrence(1000, 100));
+
+        let mut game = Game::new(vec![0, 3, 6]);
+        game.nth_number(2020);
+        let gap = game.largest_gap().unwrap();
+        // The gap's length gets spoken on the following turn
+        assert_eq!(
+            Some(gap.length),
+            Game::new(vec![0, 3, 6]).nth_number(gap.turn + 1)
+        );
+    }
+
+    #[test]
+    fn turns_past_u32_test() {
+        // A few billion turns in, turns no longer fit in the Vec
+        let mut game = Game::new(vec![]);
+        game.remember(3, 10);
+        assert_eq!(Some(10), game.last_spoken_on(3));
+        let late = u32::MAX as usize + 7;
+        game.remember(3, late);
+        assert_eq!(Some(late), game.last_spoken_on(3));
+        assert_eq!(0, game.dense_spoken_on[3]);
+        game.remember(5, late + 1);
+        assert_eq!(Some(late + 1), game.last_spoken_on(5));
+        assert_eq!(None, game.last_spoken_on(4));
+    }
 }
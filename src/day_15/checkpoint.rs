@@ -0,0 +1,186 @@
+use anyhow::{Context, Result};
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use super::{Game, Gap};
+
+// A Game snapshot is a small binary file, since the Vec of turns can run to hundreds of MB:
+//
+//   magic, format version
+//   dense limit, turns, last number (u64::MAX for none), zeros
+//   largest gap: number, turn, length (all u64::MAX for none)
+//   initial numbers: count, then each one
+//   dense turns: count, then each one as a u32
+//   sparse turns: count, then number and turn pairs
+//
+// Everything is little endian, and everything but the dense turns is a u64.
+const MAGIC: &[u8; 8] = b"AOC20D15";
+const VERSION: u64 = 1;
+const NONE: u64 = u64::MAX;
+
+impl Game {
+    // Writes next to `path` first and only then moves over it, so a save that fails part way
+    // leaves the last checkpoint as it was
+    pub(super) fn save(&self, path: &Path) -> Result<()> {
+        let temporary = path.with_extension("tmp");
+        let file = File::create(&temporary)
+            .with_context(|| format!("creating {}", temporary.display()))?;
+        let mut out = BufWriter::new(file);
+        self.write_to(&mut out)?;
+        let file = out.into_inner().map_err(|e| e.into_error())?;
+        file.sync_all()?;
+        fs::rename(&temporary, path).with_context(|| {
+            format!("replacing {} with {}", path.display(), temporary.display())
+        })?;
+        Ok(())
+    }
+
+    pub(super) fn load(path: &Path) -> Result<Game> {
+        let file = File::open(path).with_context(|| format!("opening {}", path.display()))?;
+        Game::read_from(&mut BufReader::new(file))
+            .with_context(|| format!("reading a day 15 checkpoint from {}", path.display()))
+    }
+
+    fn write_to<W: Write>(&self, out: &mut W) -> Result<()> {
+        let u64 = |out: &mut W, n: u64| out.write_all(&n.to_le_bytes());
+        out.write_all(MAGIC)?;
+        u64(out, VERSION)?;
+        u64(out, self.dense_limit as u64)?;
+        u64(out, self.turns as u64)?;
+        u64(out, self.last_number.map_or(NONE, |n| n as u64))?;
+        u64(out, self.zeros as u64)?;
+        match self.largest_gap {
+            Some(gap) => {
+                u64(out, gap.number as u64)?;
+                u64(out, gap.turn as u64)?;
+                u64(out, gap.length as u64)?;
+            }
+            None => {
+                for _ in 0..3 {
+                    u64(out, NONE)?;
+                }
+            }
+        }
+        u64(out, self.initial_numbers.len() as u64)?;
+        for n in self.initial_numbers.iter() {
+            u64(out, *n as u64)?;
+        }
+        u64(out, self.dense_spoken_on.len() as u64)?;
+        for turn in self.dense_spoken_on.iter() {
+            out.write_all(&turn.to_le_bytes())?;
+        }
+        // Sorted so the same game always saves to the same bytes
+        let mut sparse: Vec<_> = self.sparse_spoken_on.iter().collect();
+        sparse.sort();
+        u64(out, sparse.len() as u64)?;
+        for (number, turn) in sparse {
+            u64(out, *number as u64)?;
+            u64(out, *turn as u64)?;
+        }
+        Ok(())
+    }
+
+    fn read_from<R: Read>(input: &mut R) -> Result<Game> {
+        let u64 = |input: &mut R| -> Result<u64> {
+            let mut bytes = [0; 8];
+            input.read_exact(&mut bytes)?;
+            Ok(u64::from_le_bytes(bytes))
+        };
+        let usize = |input: &mut R| -> Result<usize> { Ok(u64(input)? as usize) };
+        let optional = |n: u64| if n == NONE { None } else { Some(n as usize) };
+
+        let mut magic = [0; 8];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            anyhow::bail!("not a day 15 checkpoint");
+        }
+        let version = u64(input)?;
+        if version != VERSION {
+            anyhow::bail!("unsupported checkpoint version {}", version);
+        }
+
+        let mut game = Game::new(vec![]);
+        game.dense_limit = usize(input)?;
+        game.turns = usize(input)?;
+        game.last_number = optional(u64(input)?);
+        game.zeros = usize(input)?;
+        let (number, turn, length) = (u64(input)?, u64(input)?, u64(input)?);
+        game.largest_gap = optional(number).map(|number| Gap {
+            number,
+            turn: turn as usize,
+            length: length as usize,
+        });
+        let initial = usize(input)?;
+        game.initial_numbers = (0..initial).map(|_| usize(input)).collect::<Result<_>>()?;
+        let dense = usize(input)?;
+        game.dense_spoken_on = (0..dense)
+            .map(|_| {
+                let mut bytes = [0; 4];
+                input.read_exact(&mut bytes)?;
+                Ok(u32::from_le_bytes(bytes))
+            })
+            .collect::<Result<_>>()?;
+        let sparse = usize(input)?;
+        for _ in 0..sparse {
+            let number = usize(input)?;
+            game.sparse_spoken_on.insert(number, usize(input)?);
+        }
+        Ok(game)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip_test() {
+        let mut game = Game::new(vec![0, 3, 6]);
+        game.dense_limit = 100;
+        game.nth_number(1000);
+        assert!(!game.sparse_spoken_on.is_empty());
+
+        let mut bytes = Vec::new();
+        game.write_to(&mut bytes).unwrap();
+        let mut resumed = Game::read_from(&mut bytes.as_slice()).unwrap();
+        assert_eq!(game, resumed);
+        assert_eq!(Some(436), game.nth_number(2020));
+        assert_eq!(Some(436), resumed.nth_number(2020));
+        assert_eq!(game, resumed);
+
+        // A fresh game saves too
+        let fresh = Game::new(vec![1, 2]);
+        let mut bytes = Vec::new();
+        fresh.write_to(&mut bytes).unwrap();
+        assert_eq!(fresh, Game::read_from(&mut bytes.as_slice()).unwrap());
+    }
+
+    #[test]
+    fn save_and_load_test() {
+        let path = std::env::temp_dir().join(format!("day_15_checkpoint_{}", std::process::id()));
+        let mut game = Game::new(vec![0, 3, 6]);
+        game.nth_number(10);
+        game.save(&path).unwrap();
+        // Saving again replaces the checkpoint, without leaving the temporary file behind
+        game.nth_number(1000);
+        game.save(&path).unwrap();
+        assert!(!path.with_extension("tmp").exists());
+        let mut resumed = Game::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(game, resumed);
+        assert_eq!(Some(436), resumed.nth_number(2020));
+    }
+
+    #[test]
+    fn bad_checkpoint_test() {
+        let error = Game::read_from(&mut &b"AOC20D15\x02\0\0\0\0\0\0\0"[..]).unwrap_err();
+        assert_eq!("unsupported checkpoint version 2", error.to_string());
+        assert!(Game::read_from(&mut &b"not a checkpoint"[..]).is_err());
+        // Cut short
+        let mut bytes = Vec::new();
+        Game::new(vec![1, 2]).write_to(&mut bytes).unwrap();
+        bytes.pop();
+        assert!(Game::read_from(&mut bytes.as_slice()).is_err());
+    }
+}
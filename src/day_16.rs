@@ -1,13 +1,19 @@
 use anyhow::Result;
 use combine::easy;
-use combine::lib::collections::{HashMap, HashSet};
+use combine::lib::collections::HashMap;
 use combine::parser::char::*;
 use combine::*;
-use std::collections::hash_map::Entry;
+use itertools::Itertools;
+use std::fmt;
 use std::num::ParseIntError;
 use std::ops::RangeInclusive;
 use std::result::Result as StdResult;
 
+use ranges::Ranges;
+
+pub mod matching;
+pub mod ranges;
+
 const INPUT: &str = include_str!("../data/day_16_input");
 
 pub fn run() -> Result<()> {
@@ -16,21 +22,23 @@ pub fn run() -> Result<()> {
     let data = parse(INPUT)?;
 
     println!("Solution 1: {:?}", data.solution_1());
-    println!("Solution 2: {:?}", data.solution_2());
+    println!("Solution 2: {}", data.solution_2()?);
 
     Ok(())
 }
 
+// Written like "class: 1-3 or 5-7", with any number of ranges. Ranges can be open at either end
+// ("60-" or "-10") or a single number, and some can be taken back out with "except", like
+// "seat: 1-100 except 13 or 40-49".
 #[derive(Debug, Eq, PartialEq)]
 struct Rule {
     name: String,
-    range_1: RangeInclusive<usize>,
-    range_2: RangeInclusive<usize>,
+    ranges: Ranges,
 }
 
 impl Rule {
     fn is_valid(&self, check: usize) -> bool {
-        self.range_1.contains(&check) || self.range_2.contains(&check)
+        self.ranges.contains(check)
     }
 }
 
@@ -46,111 +54,152 @@ struct Data {
 
 impl Data {
     fn solution_1(&self) -> usize {
+        let any_rule = self.any_rule();
         self.nearby_tickets
             .iter()
-            .flat_map(|ticket| {
-                ticket
-                    .0
-                    .iter()
-                    .filter(|value| !self.rules.iter().any(|rule| rule.is_valid(**value)))
-            })
+            .flat_map(|ticket| ticket.0.iter().filter(|value| !any_rule.contains(**value)))
             .sum()
     }
 
-    fn solution_2(&self) -> usize {
-        let mappings = self.field_idx_to_valid_rules();
+    // Values that are valid for at least one rule
+    fn any_rule(&self) -> Ranges {
+        self.rules
+            .iter()
+            .fold(Ranges::default(), |acc, rule| acc.union(&rule.ranges))
+    }
+
+    fn solution_2(&self) -> StdResult<usize, AssignmentError> {
+        let mappings = self.field_idx_to_valid_rules()?;
         let mappings_that_start_with_departure = mappings
             .iter()
             .filter(|(_, (_, rule_name))| rule_name.starts_with("departure"));
-        mappings_that_start_with_departure.fold(1, |acc, (field_idx, _)| {
-            if let Some(value) = self.your_ticket.0.get(*field_idx) {
-                acc * value
-            } else {
-                acc
-            }
-        })
+        Ok(
+            mappings_that_start_with_departure.fold(1, |acc, (field_idx, _)| {
+                if let Some(value) = self.your_ticket.0.get(*field_idx) {
+                    acc * value
+                } else {
+                    acc
+                }
+            }),
+        )
     }
 
-    fn field_idx_to_valid_rules(&self) -> HashMap<usize, (usize, &str)> {
-        // prune invalid tickets
+    // Gives every field on the tickets its own rule, such that the rule holds for that field on
+    // all the valid nearby tickets. There has to be exactly one way to do it.
+    fn field_idx_to_valid_rules(
+        &self,
+    ) -> StdResult<HashMap<usize, (usize, &str)>, AssignmentError> {
+        let candidates = self.candidate_rules();
+        let matching = matching::maximum_matching(&candidates, self.rules.len());
 
-        let valid_tickets = self.nearby_tickets.iter().filter(|ticket| {
-            let first_invalid_value = ticket
-                .0
-                .iter()
-                .find(|value| !self.rules.iter().any(|rule| rule.is_valid(**value)));
-            first_invalid_value.is_none()
-        });
+        let unassigned: Vec<_> = matching
+            .iter()
+            .enumerate()
+            .filter(|(_, rule_idx)| rule_idx.is_none())
+            .map(|(field_idx, _)| field_idx)
+            .collect();
+        if !unassigned.is_empty() {
+            return Err(AssignmentError::NoPerfectMatching { unassigned });
+        }
 
-        let mut value_idx_to_possible_rule_names =
-            valid_tickets.fold(HashMap::new(), |mut acc, ticket| {
-                for (field_idx, value) in ticket.0.iter().enumerate() {
-                    let valid_rule_names = self
-                        .rules
-                        .iter()
-                        .enumerate()
-                        .filter_map(|(rule_idx, rule)| {
-                            if rule.is_valid(*value) {
-                                Some((rule_idx, rule.name.as_ref()))
-                            } else {
-                                None
-                            }
-                        })
+        let alternatives = matching::alternatives(&candidates, self.rules.len(), &matching);
+        if alternatives.iter().any(|others| !others.is_empty()) {
+            let alternatives = alternatives
+                .into_iter()
+                .zip(matching.iter())
+                .enumerate()
+                .filter(|(_, (others, _))| !others.is_empty())
+                .map(|(field_idx, (others, rule_idx))| {
+                    let mut rule_idxs: Vec<_> = rule_idx.iter().copied().chain(others).collect();
+                    rule_idxs.sort_unstable();
+                    let names = rule_idxs
+                        .into_iter()
+                        .map(|rule_idx| self.rules[rule_idx].name.clone())
                         .collect();
-                    match acc.entry(field_idx) {
-                        Entry::Occupied(existing) => {
-                            let existing_entry: &mut HashSet<(usize, &str)> = existing.into_mut();
-                            *existing_entry = existing_entry
-                                .intersection(&valid_rule_names)
-                                .copied()
-                                .collect();
-                        }
-                        Entry::Vacant(v) => {
-                            v.insert(valid_rule_names);
-                        }
-                    }
-                }
-                acc
-            });
-
-        while value_idx_to_possible_rule_names
-            .iter()
-            .any(|(_, v)| v.len() > 1)
-        {
-            let field_idx_to_single_rule_idx: HashMap<_, _> = value_idx_to_possible_rule_names
-                .iter()
-                .filter_map(|(k, v)| {
-                    if v.len() == 1 {
-                        v.iter().last().copied().map(|p| (*k, p.0))
-                    } else {
-                        None
-                    }
+                    (field_idx, names)
                 })
                 .collect();
-            let rule_idx_bound_to_single_field_idx: HashSet<usize> =
-                field_idx_to_single_rule_idx.values().copied().collect();
-            for (_, rule_idx_and_name) in value_idx_to_possible_rule_names
-                .iter_mut()
-                .filter(|(field_idx, _)| !field_idx_to_single_rule_idx.contains_key(field_idx))
-            {
-                rule_idx_and_name
-                    .retain(|(rule_idx, _)| !rule_idx_bound_to_single_field_idx.contains(rule_idx))
-            }
+            return Err(AssignmentError::Ambiguous { alternatives });
         }
 
-        value_idx_to_possible_rule_names
+        Ok(matching
             .into_iter()
-            .filter_map(|(k, v)| {
-                if let Some(v) = v.into_iter().last() {
-                    Some((k, v))
-                } else {
-                    None
-                }
+            .enumerate()
+            .filter_map(|(field_idx, rule_idx)| {
+                rule_idx.map(|rule_idx| (field_idx, (rule_idx, self.rules[rule_idx].name.as_ref())))
+            })
+            .collect())
+    }
+
+    // For each field, the rules that hold for it on every valid nearby ticket
+    fn candidate_rules(&self) -> Vec<Vec<usize>> {
+        // prune invalid tickets
+
+        let any_rule = self.any_rule();
+        let valid_tickets: Vec<_> = self
+            .nearby_tickets
+            .iter()
+            .filter(|ticket| ticket.0.iter().all(|value| any_rule.contains(*value)))
+            .collect();
+
+        (0..self.your_ticket.0.len())
+            .map(|field_idx| {
+                self.rules
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, rule)| {
+                        valid_tickets.iter().all(|ticket| {
+                            ticket
+                                .0
+                                .get(field_idx)
+                                .map_or(true, |value| rule.is_valid(*value))
+                        })
+                    })
+                    .map(|(rule_idx, _)| rule_idx)
+                    .collect()
             })
             .collect()
     }
 }
 
+#[derive(Debug, PartialEq, Eq)]
+enum AssignmentError {
+    // Fields left without a rule by a largest possible assignment
+    NoPerfectMatching {
+        unassigned: Vec<usize>,
+    },
+    // The fields that could go more than one way, with every rule each one could have
+    Ambiguous {
+        alternatives: Vec<(usize, Vec<String>)>,
+    },
+}
+
+impl fmt::Display for AssignmentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AssignmentError::NoPerfectMatching { unassigned } => write!(
+                f,
+                "no way to give every field its own rule, fields left over: {}",
+                unassigned.iter().join(", ")
+            ),
+            AssignmentError::Ambiguous { alternatives } => write!(
+                f,
+                "more than one way to assign the rules: {}",
+                alternatives
+                    .iter()
+                    .map(|(field_idx, names)| format!(
+                        "field {} could be {}",
+                        field_idx,
+                        names.join(" or ")
+                    ))
+                    .join(", ")
+            ),
+        }
+    }
+}
+
+impl std::error::Error for AssignmentError {}
+
 fn parse(s: &str) -> StdResult<Data, easy::ParseError<&str>> {
     // ugh
 
@@ -197,28 +246,58 @@ where
         <Input as combine::StreamOnce>::Position,
     >,
 {
+    // Only spaces, so a separator never runs on into the next line
+    let separator = |word: &'static str| {
+        attempt(
+            skip_many1(char(' '))
+                .with(string(word))
+                .skip(skip_many1(char(' '))),
+        )
+    };
+    let range_list = sep_by1(range_parser(), separator("or"));
     let rule_parser = many::<String, _, _>(letter().or(space()))
         .skip(char(':'))
         .skip(spaces())
-        .and(number_parser())
-        .skip(char('-'))
-        .and(number_parser())
-        .skip(spaces())
-        .skip(string("or"))
-        .skip(spaces())
-        .and(number_parser())
-        .skip(char('-'))
-        .and(number_parser())
+        .and(range_list)
+        .and(optional(
+            separator("except").with(sep_by1(range_parser(), separator("or"))),
+        ))
         .map(
-            |((((name, range_1_start), range_1_end), range_2_start), range_2_end)| Rule {
+            |((name, include), exclude): ((_, Vec<_>), Option<Vec<_>>)| Rule {
                 name,
-                range_1: range_1_start..=range_1_end,
-                range_2: range_2_start..=range_2_end,
+                ranges: Ranges::new(include, exclude.unwrap_or_default()),
             },
         );
     sep_by1(rule_parser, (newline(), not_followed_by(newline())))
 }
 
+// "a-b", "a-" (a and up), "-b" (up to b) or just "a"
+fn range_parser<Input>() -> impl Parser<Input, Output = RangeInclusive<usize>>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+    <<Input as StreamOnce>::Error as combine::ParseError<
+        char,
+        <Input as StreamOnce>::Range,
+        <Input as StreamOnce>::Position,
+    >>::StreamError: From<ParseIntError>,
+    <Input as combine::StreamOnce>::Error: combine::ParseError<
+        char,
+        <Input as combine::StreamOnce>::Range,
+        <Input as combine::StreamOnce>::Position,
+    >,
+{
+    let from = number_parser()
+        .and(optional(char('-').with(optional(number_parser()))))
+        .map(|(start, end)| match end {
+            None => start..=start,
+            Some(None) => start..=usize::MAX,
+            Some(Some(end)) => start..=end,
+        });
+    let up_to = char('-').with(number_parser()).map(|end| 0..=end);
+    attempt(from).or(up_to)
+}
+
 fn number_parser<Input>() -> impl Parser<Input, Output = usize>
 where
     Input: Stream<Token = char>,
@@ -256,6 +335,33 @@ seat: 13-40 or 45-50";
         assert_eq!(3, r.len())
     }
 
+    #[test]
+    fn general_rules_parse_test() {
+        let input = "class: 1-3 or 5-7 or 9
+row: 60- except 70-79 or 90
+seat: -10 or 20-30 except 5 or 25-
+order: 1-2";
+        let mut parser = rules_parser();
+        let (r, rest) = parser.easy_parse(input).unwrap();
+        assert_eq!("", rest);
+        let ranges: Vec<Vec<_>> = r
+            .iter()
+            .map(|rule| rule.ranges.iter().cloned().collect())
+            .collect();
+        assert_eq!(
+            vec![
+                vec![1..=3, 5..=7, 9..=9],
+                vec![60..=69, 80..=89, 91..=usize::MAX],
+                vec![0..=4, 6..=10, 20..=24],
+                vec![1..=2],
+            ],
+            ranges
+        );
+        assert_eq!("order", r[3].name);
+        assert!(r[1].is_valid(1_000_000));
+        assert!(!r[1].is_valid(75));
+    }
+
     #[test]
     fn your_ticket_parse_test() {
         let input = "your ticket:
@@ -289,18 +395,15 @@ nearby tickets:
             rules: vec![
                 Rule {
                     name: "class".to_string(),
-                    range_1: 1..=3,
-                    range_2: 5..=7,
+                    ranges: Ranges::new(vec![1..=3, 5..=7], vec![]),
                 },
                 Rule {
                     name: "row".to_string(),
-                    range_1: 6..=11,
-                    range_2: 33..=44,
+                    ranges: Ranges::new(vec![6..=11, 33..=44], vec![]),
                 },
                 Rule {
                     name: "seat".to_string(),
-                    range_1: 13..=40,
-                    range_2: 45..=50,
+                    ranges: Ranges::new(vec![13..=40, 45..=50], vec![]),
                 },
             ],
 
@@ -349,10 +452,68 @@ nearby tickets:
 5,14,9
 ";
         let data = parse(input).unwrap();
-        let mappings = data.field_idx_to_valid_rules();
+        let mappings = data.field_idx_to_valid_rules().unwrap();
 
         assert_eq!("row", mappings.get(&0).unwrap().1);
         assert_eq!("class", mappings.get(&1).unwrap().1);
         assert_eq!("seat", mappings.get(&2).unwrap().1);
     }
+
+    #[test]
+    fn ambiguous_fields_test() {
+        // No field is down to a single rule to start eliminating from
+        let input = "class: 0-10
+row: 0-10
+seat: 0-5
+
+your ticket:
+1,2,3
+
+nearby tickets:
+1,2,3
+9,8,4";
+        let data = parse(input).unwrap();
+        let error = data.field_idx_to_valid_rules().unwrap_err();
+        assert_eq!(
+            AssignmentError::Ambiguous {
+                alternatives: vec![
+                    (0, vec!["class".to_string(), "row".to_string()]),
+                    (1, vec!["class".to_string(), "row".to_string()]),
+                ]
+            },
+            error
+        );
+        assert_eq!(
+            "more than one way to assign the rules: field 0 could be class or row, field 1 could \
+             be class or row",
+            error.to_string()
+        );
+    }
+
+    #[test]
+    fn impossible_fields_test() {
+        let input = "class: 0-10
+row: 0-5
+seat: 0-5
+
+your ticket:
+1,2,3
+
+nearby tickets:
+1,2,3
+4,9,8";
+        let data = parse(input).unwrap();
+        let error = data.field_idx_to_valid_rules().unwrap_err();
+        assert_eq!(
+            AssignmentError::NoPerfectMatching {
+                unassigned: vec![2]
+            },
+            error
+        );
+        assert_eq!(
+            "no way to give every field its own rule, fields left over: 2",
+            error.to_string()
+        );
+        assert!(data.solution_2().is_err());
+    }
 }
@@ -0,0 +1,251 @@
+// Bipartite matching between "left" and "right" vertices, numbered from 0, where `adjacency[l]`
+// lists the right vertices left vertex `l` can be matched with
+
+// Hopcroft–Karp: each phase finds a maximal set of shortest augmenting paths that don't share
+// vertices, and there are only O(√V) phases, so it takes O(E √V) overall
+pub fn maximum_matching(adjacency: &[Vec<usize>], right_len: usize) -> Vec<Option<usize>> {
+    let mut state = State::new(adjacency, right_len);
+    while state.layer() {
+        for left in 0..adjacency.len() {
+            if state.left_match[left].is_none() {
+                state.augment_along_layers(left);
+            }
+        }
+    }
+    state.left_match
+}
+
+// Given a matching covering every left vertex, the right vertices each left vertex could have
+// instead in some other matching covering every left vertex. All empty means `matching` is the
+// only one.
+pub fn alternatives(
+    adjacency: &[Vec<usize>],
+    right_len: usize,
+    matching: &[Option<usize>],
+) -> Vec<Vec<usize>> {
+    let mut state = State::new(adjacency, right_len);
+    for (left, right) in matching.iter().enumerate() {
+        if let Some(right) = *right {
+            state.left_match[left] = Some(right);
+            state.right_match[right] = Some(left);
+        }
+    }
+    adjacency
+        .iter()
+        .enumerate()
+        .map(|(left, rights)| {
+            rights
+                .iter()
+                .copied()
+                .filter(|right| matching[left] != Some(*right))
+                .filter(|right| state.clone().can_swap_to(left, *right))
+                .collect()
+        })
+        .collect()
+}
+
+#[derive(Clone)]
+struct State<'a> {
+    adjacency: &'a [Vec<usize>],
+    left_match: Vec<Option<usize>>,
+    right_match: Vec<Option<usize>>,
+    // Layer of each left vertex in the current phase, None when it isn't on a shortest path
+    layers: Vec<Option<usize>>,
+    // The layer the shortest augmenting paths end on, with an edge to an unmatched right vertex
+    last_layer: Option<usize>,
+}
+
+impl<'a> State<'a> {
+    fn new(adjacency: &'a [Vec<usize>], right_len: usize) -> State<'a> {
+        State {
+            adjacency,
+            left_match: vec![None; adjacency.len()],
+            right_match: vec![None; right_len],
+            layers: vec![None; adjacency.len()],
+            last_layer: None,
+        }
+    }
+
+    // Breadth first from the unmatched left vertices along alternating paths, stopping at the
+    // first layer that reaches an unmatched right vertex. Returns whether there was one, ie.
+    // whether there's an augmenting path.
+    fn layer(&mut self) -> bool {
+        let mut queue = std::collections::VecDeque::new();
+        for (left, matched) in self.left_match.iter().enumerate() {
+            if matched.is_none() {
+                self.layers[left] = Some(0);
+                queue.push_back(left);
+            } else {
+                self.layers[left] = None;
+            }
+        }
+        self.last_layer = None;
+        while let Some(left) = queue.pop_front() {
+            let layer = self.layers[left].unwrap_or(0);
+            if self.last_layer.is_some_and(|last| layer > last) {
+                break;
+            }
+            for right in self.adjacency[left].iter() {
+                match self.right_match[*right] {
+                    None => self.last_layer = Some(layer),
+                    Some(next) if self.layers[next].is_none() => {
+                        self.layers[next] = Some(layer + 1);
+                        queue.push_back(next);
+                    }
+                    Some(_) => {}
+                }
+            }
+        }
+        self.last_layer.is_some()
+    }
+
+    // Depth first along the layers, so only ever finds a shortest augmenting path
+    fn augment_along_layers(&mut self, left: usize) -> bool {
+        let (layer, last_layer) = match (self.layers[left], self.last_layer) {
+            (Some(layer), Some(last_layer)) => (layer, last_layer),
+            _ => return false,
+        };
+        for right in self.adjacency[left].iter().copied() {
+            let augmented = match self.right_match[right] {
+                None => layer == last_layer,
+                Some(next) => {
+                    layer < last_layer
+                        && self.layers[next] == Some(layer + 1)
+                        && self.augment_along_layers(next)
+                }
+            };
+            if augmented {
+                self.left_match[left] = Some(right);
+                self.right_match[right] = Some(left);
+                return true;
+            }
+        }
+        // Dead end for the rest of this phase
+        self.layers[left] = None;
+        false
+    }
+
+    // Matches `left` with `right` instead, then looks for somewhere else for whatever had
+    // `right`, without taking `right` back
+    fn can_swap_to(&mut self, left: usize, right: usize) -> bool {
+        if let Some(previous) = self.left_match[left] {
+            self.right_match[previous] = None;
+        }
+        let displaced = self.right_match[right];
+        self.left_match[left] = Some(right);
+        self.right_match[right] = Some(left);
+        match displaced {
+            None => true,
+            Some(displaced) => {
+                self.left_match[displaced] = None;
+                let mut visited = vec![false; self.right_match.len()];
+                visited[right] = true;
+                self.augment(displaced, &mut visited)
+            }
+        }
+    }
+
+    // Plain depth first search for an augmenting path
+    fn augment(&mut self, left: usize, visited: &mut [bool]) -> bool {
+        for right in self.adjacency[left].iter().copied() {
+            if visited[right] {
+                continue;
+            }
+            visited[right] = true;
+            let augmented = match self.right_match[right] {
+                None => true,
+                Some(next) => self.augment(next, visited),
+            };
+            if augmented {
+                self.left_match[left] = Some(right);
+                self.right_match[right] = Some(left);
+                return true;
+            }
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_matching(adjacency: &[Vec<usize>], matching: &[Option<usize>]) -> bool {
+        let mut used = std::collections::HashSet::new();
+        matching
+            .iter()
+            .enumerate()
+            .all(|(left, right)| match right {
+                Some(right) => adjacency[left].contains(right) && used.insert(*right),
+                None => true,
+            })
+    }
+
+    #[test]
+    fn maximum_matching_test() {
+        // Greedily taking the first option leaves vertex 2 out
+        let adjacency = vec![vec![0, 1], vec![0], vec![1, 2], vec![2]];
+        let matching = maximum_matching(&adjacency, 3);
+        assert!(is_matching(&adjacency, &matching));
+        assert_eq!(3, matching.iter().flatten().count());
+
+        let adjacency = vec![vec![0, 1, 2], vec![0], vec![1], vec![0, 1]];
+        let matching = maximum_matching(&adjacency, 3);
+        assert!(is_matching(&adjacency, &matching));
+        assert_eq!(3, matching.iter().flatten().count());
+        assert_eq!(Some(2), matching[0]);
+
+        let adjacency = vec![vec![], vec![0]];
+        assert_eq!(vec![None, Some(0)], maximum_matching(&adjacency, 1));
+    }
+
+    #[test]
+    fn matches_simple_augmenting_test() {
+        // Against one augmenting path at a time, on graphs from a little LCG
+        let mut seed: u64 = 12345;
+        let mut next = move |n: u64| {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            ((seed >> 33) % n) as usize
+        };
+        for _ in 0..200 {
+            let (left_len, right_len) = (next(12) + 1, next(12) + 1);
+            let adjacency: Vec<Vec<usize>> = (0..left_len)
+                .map(|_| (0..right_len).filter(|_| next(4) == 0).collect())
+                .collect();
+            let matching = maximum_matching(&adjacency, right_len);
+            assert!(is_matching(&adjacency, &matching));
+
+            let mut simple = State::new(&adjacency, right_len);
+            let simple_size = (0..left_len)
+                .filter(|left| simple.augment(*left, &mut vec![false; right_len]))
+                .count();
+            assert_eq!(simple_size, matching.iter().flatten().count());
+        }
+    }
+
+    #[test]
+    fn alternatives_test() {
+        let adjacency = vec![vec![0, 1], vec![0, 1], vec![1, 2]];
+        let matching = maximum_matching(&adjacency, 3);
+        assert_eq!(Some(2), matching[2]);
+        let others = alternatives(&adjacency, 3, &matching);
+        assert_eq!(1, others[0].len());
+        assert_eq!(1, others[1].len());
+        // Taking 1 would leave nothing for the other two
+        assert!(others[2].is_empty());
+
+        let adjacency = vec![vec![0, 1], vec![1]];
+        let matching = maximum_matching(&adjacency, 2);
+        assert_eq!(vec![Some(0), Some(1)], matching);
+        assert_eq!(
+            vec![Vec::<usize>::new(), vec![]],
+            alternatives(&adjacency, 2, &matching)
+        );
+
+        // A spare right vertex is an alternative too
+        let adjacency = vec![vec![0, 1]];
+        assert_eq!(vec![vec![1]], alternatives(&adjacency, 2, &[Some(0)]));
+    }
+}
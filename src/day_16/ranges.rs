@@ -0,0 +1,121 @@
+use std::ops::RangeInclusive;
+
+// A set of numbers as sorted intervals that neither overlap nor touch, so checking a number is a
+// binary search
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Ranges(Vec<RangeInclusive<usize>>);
+
+impl Ranges {
+    // Everything in `include` that isn't in `exclude`. Backwards ranges are empty.
+    pub fn new<I, E>(include: I, exclude: E) -> Ranges
+    where
+        I: IntoIterator<Item = RangeInclusive<usize>>,
+        E: IntoIterator<Item = RangeInclusive<usize>>,
+    {
+        let mut ranges = merge(include);
+        for excluded in merge(exclude) {
+            ranges = ranges
+                .into_iter()
+                .flat_map(|range| subtract(range, &excluded))
+                .collect();
+        }
+        Ranges(ranges)
+    }
+
+    pub fn contains(&self, n: usize) -> bool {
+        let idx = self.0.partition_point(|range| *range.end() < n);
+        self.0.get(idx).is_some_and(|range| *range.start() <= n)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &RangeInclusive<usize>> {
+        self.0.iter()
+    }
+
+    // Every number in either
+    pub fn union(&self, other: &Ranges) -> Ranges {
+        Ranges(merge(self.iter().chain(other.iter()).cloned()))
+    }
+}
+
+fn merge<I>(ranges: I) -> Vec<RangeInclusive<usize>>
+where
+    I: IntoIterator<Item = RangeInclusive<usize>>,
+{
+    let mut ranges: Vec<_> = ranges.into_iter().filter(|r| !r.is_empty()).collect();
+    ranges.sort_by_key(|r| *r.start());
+    let mut merged: Vec<RangeInclusive<usize>> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if *range.start() <= last.end().saturating_add(1) => {
+                if range.end() > last.end() {
+                    *last = *last.start()..=*range.end();
+                }
+            }
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
+
+fn subtract(
+    range: RangeInclusive<usize>,
+    excluded: &RangeInclusive<usize>,
+) -> Vec<RangeInclusive<usize>> {
+    let (start, end) = range.into_inner();
+    if *excluded.end() < start || end < *excluded.start() {
+        return vec![start..=end];
+    }
+    let mut left = Vec::with_capacity(2);
+    if start < *excluded.start() {
+        left.push(start..=*excluded.start() - 1);
+    }
+    if *excluded.end() < end {
+        left.push(*excluded.end() + 1..=end);
+    }
+    left
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merge_test() {
+        let ranges = Ranges::new(
+            vec![10..=20, 1..=3, 4..=5, 15..=25, RangeInclusive::new(30, 29)],
+            vec![],
+        );
+        assert_eq!(
+            vec![&(1..=5), &(10..=25)],
+            ranges.iter().collect::<Vec<_>>()
+        );
+        assert!(ranges.contains(1));
+        assert!(ranges.contains(5));
+        assert!(!ranges.contains(6));
+        assert!(ranges.contains(17));
+        assert!(!ranges.contains(26));
+        assert!(!ranges.contains(0));
+        assert!(!Ranges::default().contains(0));
+    }
+
+    #[test]
+    fn exclude_test() {
+        let ranges = Ranges::new(vec![0..=usize::MAX], vec![5..=9, 7..=12, 100..=100]);
+        assert_eq!(
+            vec![&(0..=4), &(13..=99), &(101..=usize::MAX)],
+            ranges.iter().collect::<Vec<_>>()
+        );
+        assert!(ranges.contains(usize::MAX));
+        assert!(!ranges.contains(10));
+
+        let nothing_left = Ranges::new(vec![3..=4, 6..=8], vec![0..=10]);
+        assert_eq!(Ranges::default(), nothing_left);
+    }
+
+    #[test]
+    fn union_test() {
+        let a = Ranges::new(vec![1..=3, 10..=12], vec![]);
+        let b = Ranges::new(vec![4..=6], vec![]);
+        assert_eq!(Ranges::new(vec![1..=6, 10..=12], vec![]), a.union(&b));
+    }
+}
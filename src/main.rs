@@ -3,7 +3,10 @@ extern crate aoc_2020;
 extern crate clap;
 
 use std::fmt::Display;
+use std::fs;
+use std::path::Path;
 use std::str::FromStr;
+use std::time::Duration;
 
 use anyhow::Result;
 use clap::{App, Arg, ArgMatches};
@@ -22,6 +25,85 @@ fn main() -> Result<()> {
                 .index(1)
                 .help("Which day's solution you want to run"),
         )
+        .arg(
+            Arg::with_name("preamble")
+                .long("preamble")
+                .takes_value(true)
+                .default_value("25")
+                .help("How many numbers make up the preamble (day 9 only)"),
+        )
+        .arg(
+            Arg::with_name("modulus")
+                .long("modulus")
+                .takes_value(true)
+                .help("Also print the number of adapter chains modulo this (day 10 only)"),
+        )
+        .arg(
+            Arg::with_name("stdin")
+                .long("stdin")
+                .help("Read numbers from stdin instead of the bundled input (day 9 only)"),
+        )
+        .arg(
+            Arg::with_name("debugger")
+                .long("debugger")
+                .help("Step through the program interactively instead (day 8 only)"),
+        )
+        .arg(
+            Arg::with_name("animate")
+                .long("animate")
+                .help("Draw every generation of the seating simulations (day 11 only)"),
+        )
+        .arg(
+            Arg::with_name("frame-delay")
+                .long("frame-delay")
+                .takes_value(true)
+                .default_value("100")
+                .help("Milliseconds between animation frames (day 11 only)"),
+        )
+        .arg(
+            Arg::with_name("svg")
+                .long("svg")
+                .takes_value(true)
+                .value_name("DIR")
+                .help("Save the ship's routes as SVG files in this directory (day 12 only)"),
+        )
+        .arg(
+            Arg::with_name("constraints")
+                .long("constraints")
+                .takes_value(true)
+                .value_name("FILE")
+                .help("Extra bus schedule constraints to solve for (day 13 only)"),
+        )
+        .arg(
+            Arg::with_name("dump")
+                .long("dump")
+                .takes_value(true)
+                .possible_values(&["hex", "json"])
+                .help(
+                    "Print the final memory in this format, for the version 1 decoder only \
+                     (day 14 only)",
+                ),
+        )
+        .arg(
+            Arg::with_name("width")
+                .long("width")
+                .takes_value(true)
+                .help("How many bits wide memory is, up to 64 rather than 36 (day 14 only)"),
+        )
+        .arg(
+            Arg::with_name("checkpoint")
+                .long("checkpoint")
+                .takes_value(true)
+                .value_name("FILE")
+                .help("Resume the game from this file and save it back there (day 15 only)"),
+        )
+        .arg(
+            Arg::with_name("turns")
+                .long("turns")
+                .takes_value(true)
+                .default_value("30000000")
+                .help("Which turn to play up to with --checkpoint (day 15 only)"),
+        )
         .get_matches();
     match get_number("day", Some(0), &matches) {
         1 => day_01::run()?,
@@ -31,14 +113,40 @@ fn main() -> Result<()> {
         5 => day_05::run()?,
         6 => day_06::run()?,
         7 => day_07::run()?,
+        8 if matches.is_present("debugger") => day_08::debug()?,
         8 => day_08::run()?,
-        9 => day_09::run()?,
-        10 => day_10::run()?,
+        9 if matches.is_present("stdin") => {
+            day_09::run_stdin(get_number("preamble", Some(0), &matches))?
+        }
+        9 => day_09::run(get_number("preamble", Some(0), &matches))?,
+        10 => day_10::run(
+            matches
+                .value_of("modulus")
+                .map(|_| get_number("modulus", Some(0), &matches)),
+        )?,
+        11 if matches.is_present("animate") => day_11::animate(Duration::from_millis(get_number(
+            "frame-delay",
+            None,
+            &matches,
+        )))?,
         11 => day_11::run()?,
-        12 => day_12::run()?,
-        13 => day_13::run()?,
-        14 => day_14::run()?,
-        15 => day_15::run()?,
+        12 => day_12::run(matches.value_of("svg").map(Path::new))?,
+        13 => match matches.value_of("constraints") {
+            Some(file) => day_13::run(Some(&fs::read_to_string(file)?))?,
+            None => day_13::run(None)?,
+        },
+        14 => day_14::run(
+            matches.value_of("dump"),
+            matches
+                .value_of("width")
+                .map(|_| get_number("width", Some(0), &matches)),
+        )?,
+        15 => match matches.value_of("checkpoint") {
+            Some(file) => {
+                day_15::run_checkpointed(Path::new(file), get_number("turns", Some(1), &matches))?
+            }
+            None => day_15::run()?,
+        },
         16 => day_16::run()?,
         17 => day_17::run()?,
         18 => day_18::run()?,